@@ -5,21 +5,22 @@ use bevy::utils::hashbrown::HashMap;
 use bevy_hanabi::prelude::*;
 
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
 use crate::input::Mouse;
 
 use crate::animation::looping_animator::LoopingAnimator;
 
-use crate::entity::{health::Health, stats::{Stats, StatType}, damage::DamageType};
+use crate::entity::{combat_log::{StatusAppliedEvent, StatusKind}, health::{DamageInstance, DotStacking, EntityType, Health, Shield, DOT, HOT}, stats::{Stats, StatModifier, StatType}, damage::{AttackerSnapshot, DamageType}};
 
 use crate::player::Player;
 
 use crate::abilities::ability_particles::{AbilityParticles, ParticleType};
 
 #[derive(Reflect)]
-pub enum EffectType { Slow, Damage, Heal, Stun }
+pub enum EffectType { Slow, Damage, Heal, Stun, Shield }
 
-#[derive(Reflect, Hash, PartialEq, Eq, Debug, Copy, Clone)]
+#[derive(Reflect, Hash, PartialEq, Eq, Debug, Copy, Clone, Deserialize)]
 pub enum AbilityType { FireBall, IceStorm, HealOrb }
 
 #[derive(Reflect)]
@@ -27,15 +28,17 @@ pub struct AbilityData {
     pub ability_type: AbilityType,
     pub cooldown: f32,
     pub magnitude: f32,
-    pub speed: f32
+    pub speed: f32,
+    /// Knockback speed applied to anything hit
+    pub weight: f32
 }
 
 impl AbilityData {
     pub fn from_type(ability_type: AbilityType) -> Self {
         match ability_type {
-            AbilityType::FireBall => AbilityData { ability_type: AbilityType::FireBall, cooldown: 2.0, magnitude: 5.0, speed: 100.0 },
-            AbilityType::IceStorm => AbilityData { ability_type: AbilityType::IceStorm, cooldown: 5.0, magnitude: 5.0, speed: 25.0 },
-            AbilityType::HealOrb => AbilityData { ability_type: AbilityType::HealOrb, cooldown: 10.0, magnitude: 10.0, speed: 0.0 }
+            AbilityType::FireBall => AbilityData { ability_type: AbilityType::FireBall, cooldown: 2.0, magnitude: 5.0, speed: 100.0, weight: 150.0 },
+            AbilityType::IceStorm => AbilityData { ability_type: AbilityType::IceStorm, cooldown: 5.0, magnitude: 5.0, speed: 25.0, weight: 0.0 },
+            AbilityType::HealOrb => AbilityData { ability_type: AbilityType::HealOrb, cooldown: 10.0, magnitude: 10.0, speed: 0.0, weight: 0.0 }
         }
     }
 }
@@ -82,18 +85,29 @@ impl Ability {
 }
 
 impl AbilitySystem {
+    pub fn with_abilities(abilities: &[AbilityType]) -> Self {
+        AbilitySystem { abilities: abilities.iter().map(|ability_type| Ability::new(*ability_type)).collect() }
+    }
+
     pub fn get_ability(&mut self, slot: usize) -> Option<&mut Ability> {
         return self.abilities.get_mut(slot);
     }
 }
 
+#[derive(Event)]
+pub struct AbilityCastEvent {
+    pub caster: Entity,
+    pub ability_type: AbilityType
+}
+
 pub struct AbilitySystemPlugin;
 
 impl Plugin for AbilitySystemPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<AbilityBundle>();
+        app.add_event::<AbilityCastEvent>();
         app.add_systems(Startup, init_abilites);
-        app.add_systems(Update, (update_abilities, cast_ability, player_heal, player_dot, player_damage, player_slow, auto_destroy_abilities, auto_destroy_entities));
+        app.add_systems(Update, (update_abilities, cast_ability, player_heal, ability_dot, ability_damage, ability_slow, auto_destroy_abilities, auto_destroy_entities));
     }
 }
 
@@ -131,9 +145,10 @@ fn init_abilites(
     ]);
 }
 pub fn update_abilities(mut query: Query<&mut AbilitySystem>, time: Res<Time>) {
-    let mut system = query.single_mut();
-    for ability in system.abilities.iter_mut() {
-        ability.update_ability(time.delta_seconds());
+    for mut system in query.iter_mut() {
+        for ability in system.abilities.iter_mut() {
+            ability.update_ability(time.delta_seconds());
+        }
     }
 }
 
@@ -151,30 +166,62 @@ fn get_ability_slot(key_code: &KeyCode) -> Option<usize> {
 }
 
 pub fn cast_ability(
-    commands: Commands,
-    ability_sprites: ResMut<AbilityBundle>,
-    ability_particles: ResMut<AbilityParticles>,
-    mut query: Query<(&mut AbilitySystem, &Transform)>,
+    mut commands: Commands,
+    mut ability_sprites: ResMut<AbilityBundle>,
+    ability_particles: Res<AbilityParticles>,
+    mut query: Query<(&mut AbilitySystem, Entity, &Transform, &Stats), With<Player>>,
     mouse: Res<Mouse>,
     keyboard: Res<ButtonInput<KeyCode>>,
+    mut ev_cast: EventWriter<AbilityCastEvent>
 ) {
-    let (mut ability_system, transform) = query.single_mut();
+    let Ok((mut ability_system, caster, transform, stats)) = query.get_single_mut() else { return; };
     let Some(slot) = get_ability_slot(
         keyboard.get_just_pressed().filter(|key_code| is_ability_key(**key_code)).next()
         .unwrap_or(&KeyCode::NonConvert)) else { return; };
     let Some(ability ) = ability_system.get_ability(slot) else { return; };
     let mouse_diff = (mouse.world_position - Vec2::new(transform.translation.x, transform.translation.y)).normalize();
     if ability.can_use() {
-        let rotation = Quat::from_axis_angle(
-            Vec3::new(0.0, 0.0, -1.0), 
-            Vec2::angle_between(mouse_diff, Vec2::new(0.0, -1.0)) + std::f32::consts::FRAC_PI_2
+        ev_cast.send(AbilityCastEvent { caster, ability_type: ability.ability_data.ability_type });
+        use_ability(
+            ability, 
+            EffectOwner(caster, EntityType::Player), 
+            transform, 
+            aim_rotation(mouse_diff), 
+            AttackerSnapshot::from_stats(stats), 
+            &mut commands, 
+            &mut ability_sprites, 
+            &ability_particles
         );
-        use_ability(ability, transform, rotation, commands, ability_sprites, ability_particles);
     }
 
 }
 
-fn use_ability(ability: &mut Ability, origin: &Transform, rotation: Quat, mut commands: Commands, mut ability_sprites: ResMut<AbilityBundle>, ability_particles: ResMut<AbilityParticles>) {
+/// Fraction of the Heal Orb's magnitude healed on pickup, the rest comes over time
+const HEAL_ORB_INSTANT: f32 = 0.5;
+/// Fraction of the Heal Orb's magnitude given as a shield on pickup
+const HEAL_ORB_SHIELD: f32 = 0.5;
+const HEAL_ORB_SHIELD_DURATION: f32 = 4.0;
+/// Seconds between Ice Storm ticks, damage per tick is scaled by this so DPS matches the magnitude
+const ICE_STORM_TICK: f32 = 0.5;
+
+/// Rotation abilities are spawned with to travel along `direction`
+pub fn aim_rotation(direction: Vec2) -> Quat {
+    Quat::from_axis_angle(
+        Vec3::new(0.0, 0.0, -1.0), 
+        Vec2::angle_between(direction, Vec2::new(0.0, -1.0)) + std::f32::consts::FRAC_PI_2
+    )
+}
+
+pub fn use_ability(
+    ability: &mut Ability, 
+    owner: EffectOwner, 
+    origin: &Transform, 
+    rotation: Quat, 
+    attacker: AttackerSnapshot, 
+    commands: &mut Commands, 
+    ability_sprites: &mut AbilityBundle, 
+    ability_particles: &AbilityParticles
+) {
     ability.cooldown_timer.set_duration(Duration::from_secs_f32(ability.ability_data.cooldown));
     ability.cooldown_timer.reset();
     if let Some(mut ability_sprite) = ability_sprites.sprites.get_mut(&ability.ability_data.ability_type).cloned() {
@@ -186,7 +233,7 @@ fn use_ability(ability: &mut Ability, origin: &Transform, rotation: Quat, mut co
                          rb, constraints, coll, sensor , vel, ability, auto_destroy
                 ) = (
                     ability_sprite, 
-                    Damage { damage_amount: ability.ability_data.magnitude, damage_type: DamageType::MAGICAL, damaged_entities: Vec::new() }, 
+                    Damage { damage_amount: ability.ability_data.magnitude, damage_type: DamageType::MAGICAL, damaged_entities: Vec::new(), attacker, knockback: ability.ability_data.weight }, 
                     LoopingAnimator::new(4, 0.2),
                     RigidBody::Dynamic,
                     LockedAxes::ROTATION_LOCKED,
@@ -199,14 +246,14 @@ fn use_ability(ability: &mut Ability, origin: &Transform, rotation: Quat, mut co
                 ability_instance.transform.rotation = rotation;
                 let Some(particle_effect) = ability_particles.particle_effects.get(&ParticleType::FireBall) else { return; };
                 let particles = commands.spawn(ParticleEffectBundle { effect: ParticleEffect::new(particle_effect.clone()), transform: Transform::from_xyz(0.0, 0.0, 1.0), ..Default::default() }).id();
-                commands.spawn((ability_instance , damage, animator, rb, constraints, coll, sensor, vel, ability, auto_destroy)).add_child(particles);
+                commands.spawn((ability_instance , damage, animator, rb, constraints, coll, sensor, vel, ability, auto_destroy, owner.clone())).add_child(particles);
             },
             AbilityType::IceStorm => {
                 let (mut ability_instance, damage_over_time , slow, 
                          rb, constraints, coll, sensor, vel, ability, auto_destroy
                 ) = (
                     ability_sprite, 
-                    DamageOverTime { tick_damage: ability.ability_data.magnitude, damage_type: DamageType::PHYSICAL, duration: 0.5 }, 
+                    DamageOverTime { dot: DOT::new(ability.ability_data.magnitude * ICE_STORM_TICK, ICE_STORM_TICK, 2.0, DamageType::PHYSICAL, DotStacking::Intensity(3)).with_attacker(attacker) }, 
                     Slow { speed_reduction: ability.ability_data.magnitude, duration: ability.ability_data.magnitude },
                     RigidBody::KinematicVelocityBased,
                     LockedAxes::ROTATION_LOCKED,
@@ -217,7 +264,7 @@ fn use_ability(ability: &mut Ability, origin: &Transform, rotation: Quat, mut co
                     AutoDestroy::new(5.0)
                 ); 
                 ability_instance.transform.rotation = rotation;
-                let ability_bundle = (ability_instance, damage_over_time , slow, rb, constraints, coll, sensor, vel, ability, auto_destroy);
+                let ability_bundle = (ability_instance, damage_over_time , slow, rb, constraints, coll, sensor, vel, ability, auto_destroy, owner.clone());
                 if let Some(particle_effect) = ability_particles.particle_effects.get(&ParticleType::IceStorm) {
                     let particles = commands.spawn(ParticleEffectBundle { effect: ParticleEffect::new(particle_effect.clone()), transform: Transform::from_xyz(0.0, 0.0, 1.0), ..Default::default() }).id();
                     commands.spawn(ability_bundle).add_child(particles);
@@ -226,11 +273,15 @@ fn use_ability(ability: &mut Ability, origin: &Transform, rotation: Quat, mut co
                 }
             },
             AbilityType::HealOrb => {
-                let (mut ability_instance, heal, rb, 
+                let (mut ability_instance, heal, shield, rb, 
                          constraints, coll, sensor, vel, ability, auto_destroy
                 ) = (
                     ability_sprite, 
-                    Heal { heal_amount: ability.ability_data.magnitude }, 
+                    Heal { 
+                        heal_amount: ability.ability_data.magnitude * HEAL_ORB_INSTANT, 
+                        over_time: Some(HOT::new(ability.ability_data.magnitude * (1.0 - HEAL_ORB_INSTANT) / 4.0, 0.5, 2.0, DotStacking::Refresh))
+                    }, 
+                    GrantShield { amount: ability.ability_data.magnitude * HEAL_ORB_SHIELD, duration: HEAL_ORB_SHIELD_DURATION },
                     RigidBody::Dynamic,
                     LockedAxes::ROTATION_LOCKED,
                     Collider::ball(4.0),
@@ -241,67 +292,111 @@ fn use_ability(ability: &mut Ability, origin: &Transform, rotation: Quat, mut co
                 );
                 ability_instance.transform.rotation = Quat::IDENTITY;
                 let particles = commands.spawn(ParticleEffectBundle { effect: ParticleEffect::new(ability_particles.particle_effects.get(&ParticleType::HealOrb).unwrap().clone()), transform: Transform::from_xyz(0.0, 0.0, 1.0), ..default() }).id();
-                commands.spawn((ability_instance , heal, rb, constraints, coll, sensor, vel, ability, auto_destroy)).add_child(particles);
+                commands.spawn((ability_instance , heal, shield, rb, constraints, coll, sensor, vel, ability, auto_destroy, owner.clone())).add_child(particles);
             }
         };
     }
 }
 
+/// Heal and shield pickups, used up by the first touch
 pub fn player_heal(
     mut commands: Commands,
-    heal_query: Query<(&Heal, Entity), (With<AbilityTag>, With<Collider>)>,
+    heal_query: Query<(Option<&Heal>, Option<&GrantShield>, Entity, &AbilityTag, &EffectOwner), (With<Collider>, Or<(With<Heal>, With<GrantShield>)>)>,
     mut player_query: Query<(&mut Health, Entity), (With<Player>, With<Collider>)>,
-    rapier: Res<RapierContext>
+    rapier: Res<RapierContext>,
+    mut ev_status: EventWriter<StatusAppliedEvent>
 ) {
     let (mut player_health, player_entity) = player_query.single_mut();
-    for (heal, heal_entity) in heal_query.iter() {
+    for (heal, shield, heal_entity, tag, owner) in heal_query.iter() {
+        // NOTE: Heals dropped by enemies are for their side, same check as `ability_damage`
+        if owner.is_hostile_to(&player_health) { continue; }
         if rapier.intersection_pair(player_entity, heal_entity).is_some() {
-            player_health.heal(heal.heal_amount);
+            if let Some(heal) = heal {
+                player_health.heal_from(heal.heal_amount, Some(owner.0), Some(tag.ability_type));
+                if let Some(hot) = heal.over_time {
+                    player_health.add_hot(hot.with_owner(owner.0).with_ability(tag.ability_type), heal_entity);
+                }
+            }
+            if let Some(shield) = shield {
+                player_health.add_shield(Shield::new(shield.amount, heal_entity).with_duration(shield.duration));
+                ev_status.send(StatusAppliedEvent { 
+                    target: player_entity, 
+                    source: Some(owner.0), 
+                    ability: Some(tag.ability_type), 
+                    status: StatusKind::Shield, 
+                    duration: shield.duration 
+                });
+            }
             commands.entity(heal_entity).despawn_recursive();
         }
     }
 }
 
-pub fn player_damage(
-    mut health_query: Query<(&mut Health, Entity), (With<Collider>, Without<Player>)>,
-    mut damage_query: Query<(&mut Damage, Entity), (With<AbilityTag>, With<Collider>)>,
+pub fn ability_damage(
+    mut health_query: Query<(&mut Health, Entity, &Transform), With<Collider>>,
+    mut damage_query: Query<(&mut Damage, Entity, &Transform, &AbilityTag, &EffectOwner), With<Collider>>,
     rapier: Res<RapierContext>
 ) {
-    for (mut enemy_health, enemy_entity) in health_query.iter_mut() {
-        for (mut damage, damage_entity) in damage_query.iter_mut() {
-            if damage.damaged_entities.contains(&enemy_entity.index()) { continue; }
+    for (mut enemy_health, enemy_entity, enemy_transform) in health_query.iter_mut() {
+        for (mut damage, damage_entity, damage_transform, tag, owner) in damage_query.iter_mut() {
+            if damage.damaged_entities.contains(&enemy_entity) || !owner.is_hostile_to(&enemy_health) { continue; }
             if rapier.intersection_pair(enemy_entity, damage_entity).is_some() {
-                enemy_health.push_damage(damage.damage_amount, damage.damage_type);
-                damage.damaged_entities.push(enemy_entity.index());
+                let direction = (enemy_transform.translation - damage_transform.translation).truncate().normalize_or_zero();
+                enemy_health.push(
+                    DamageInstance::new(damage.damage_amount, damage.damage_type, true)
+                        .with_attacker(damage.attacker)
+                        .with_source(owner.0)
+                        .with_ability(tag.ability_type)
+                        .with_knockback(direction * damage.knockback)
+                );
+                damage.damaged_entities.push(enemy_entity);
                 break;
             }
         }
     }
 }
 
-pub fn player_dot(
-    mut health_query: Query<(&mut Health, Entity), (With<Collider>, Without<Player>)>,
-    damage_query: Query<(&DamageOverTime, Entity), (With<AbilityTag>, With<Collider>)>,
-    rapier: Res<RapierContext>
+pub fn ability_dot(
+    mut health_query: Query<(&mut Health, Entity), With<Collider>>,
+    damage_query: Query<(&DamageOverTime, Entity, &AbilityTag, &EffectOwner), With<Collider>>,
+    rapier: Res<RapierContext>,
+    mut ev_status: EventWriter<StatusAppliedEvent>
 ) {
     for (mut enemy_health, enemy_entity) in health_query.iter_mut() {
-        for (dot, damage_entity) in damage_query.iter() {
-            if rapier.intersection_pair(enemy_entity, damage_entity).is_some() {
-                enemy_health.add_dot(dot.tick_damage, dot.duration, dot.damage_type, damage_entity.index());
+        for (dot, damage_entity, tag, owner) in damage_query.iter() {
+            if !owner.is_hostile_to(&enemy_health) || rapier.intersection_pair(enemy_entity, damage_entity).is_none() { continue; }
+            if enemy_health.add_dot(dot.dot.with_owner(owner.0).with_ability(tag.ability_type), damage_entity) {
+                ev_status.send(StatusAppliedEvent { 
+                    target: enemy_entity, 
+                    source: Some(owner.0), 
+                    ability: Some(tag.ability_type), 
+                    status: StatusKind::DamageOverTime, 
+                    duration: dot.dot.duration 
+                });
             }
         }
     }
 }
 
-pub fn player_slow(
-    mut stat_query: Query<(&mut Stats, Entity), (With<Collider>, Without<Player>)>,
-    slow_query: Query<(&Slow, Entity), (With<AbilityTag>, With<Collider>)>,
-    rapier: Res<RapierContext>
+pub fn ability_slow(
+    mut stat_query: Query<(&mut Stats, &Health, Entity), With<Collider>>,
+    slow_query: Query<(&Slow, Entity, &AbilityTag, &EffectOwner), With<Collider>>,
+    rapier: Res<RapierContext>,
+    mut ev_status: EventWriter<StatusAppliedEvent>
 ) {
-    for (mut enemy_stats, enemy_entity) in stat_query.iter_mut() {
-        for (slow, slow_entity) in slow_query.iter() {
-            if rapier.intersection_pair(enemy_entity, slow_entity).is_some() {
-                enemy_stats.add_duration_change(StatType::Speed, -slow.speed_reduction, slow.duration, slow_entity.index(), false);
+    for (mut enemy_stats, enemy_health, enemy_entity) in stat_query.iter_mut() {
+        for (slow, slow_entity, tag, owner) in slow_query.iter() {
+            if owner.is_hostile_to(enemy_health) && rapier.intersection_pair(enemy_entity, slow_entity).is_some() {
+                if !enemy_stats.has_modifier(StatType::Speed, slow_entity) {
+                    ev_status.send(StatusAppliedEvent { 
+                        target: enemy_entity, 
+                        source: Some(owner.0), 
+                        ability: Some(tag.ability_type), 
+                        status: StatusKind::Slow, 
+                        duration: slow.duration 
+                    });
+                }
+                enemy_stats.add_modifier(StatType::Speed, StatModifier::flat(-slow.speed_reduction, slow_entity).with_duration(slow.duration));
             }
         }
     }
@@ -319,9 +414,29 @@ impl Default for AbilitySystem {
     }
 }
 
+/// The entity that cast an ability and which side it is on, used for kill credit, 
+/// combat logs and deciding what the ability can hurt
+#[derive(Component, Reflect, Clone)]
+pub struct EffectOwner(pub Entity, pub EntityType);
+
+impl EffectOwner {
+    pub fn is_hostile_to(&self, target: &Health) -> bool {
+        self.1.is_hostile_to(target.entity_type())
+    }
+}
+
 #[derive(Component)]
 pub struct Heal {
-    pub heal_amount: f32
+    pub heal_amount: f32,
+    /// Applied on pickup alongside the instant heal
+    pub over_time: Option<HOT>
+}
+
+/// Absorbs `amount` damage for `duration` seconds, see `health::Shield`
+#[derive(Component)]
+pub struct GrantShield {
+    pub amount: f32,
+    pub duration: f32
 }
 
 #[derive(Component)]
@@ -334,14 +449,15 @@ pub struct Slow {
 pub struct Damage {
     pub damage_amount: f32,
     pub damage_type: DamageType,
-    pub damaged_entities: Vec<u32>
+    pub damaged_entities: Vec<Entity>,
+    pub attacker: AttackerSnapshot,
+    pub knockback: f32
 }
 
 #[derive(Component)]
 pub struct DamageOverTime {
-    pub tick_damage: f32,
-    pub damage_type: DamageType,
-    pub duration: f32
+    /// Template copied onto anything the ability overlaps
+    pub dot: DOT
 }
 
 #[derive(Component)]
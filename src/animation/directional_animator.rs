@@ -1,10 +1,11 @@
 use bevy::prelude::*;
 use bevy::utils::hashbrown::HashMap;
+use serde::Deserialize;
 use super::*;
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Reflect)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Reflect, Deserialize)]
 pub enum AnimationType { Idle, Walk, Run, Attack, SpecialCast }
-#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Reflect)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Reflect, Deserialize)]
 pub enum AnimationDirection { Up, Down, Left, Right }
 
 pub fn vec2_to_direction(vector: &Vec2) -> AnimationDirection {
@@ -0,0 +1,297 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::abilities::abilities::{AbilityCastEvent, AbilityType};
+use super::health::{HealthDamageEvent, HealthDeathEvent, HealthHealEvent};
+
+/// Oldest entries are dropped past this so long sessions don't grow forever
+const MAX_ENTRIES: usize = 20000;
+const EXPORT_DIR: &str = "logs";
+
+pub struct CombatLogPlugin;
+
+impl Plugin for CombatLogPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CombatLog>()
+            .add_event::<StatusAppliedEvent>()
+            .add_systems(Update, (
+                (log_casts, log_damage, log_heals, log_statuses, log_kills).chain(),
+                export_combat_log
+            ));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum CombatLogKind { Damage, Heal, Status, Kill, Cast }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Reflect)]
+pub enum StatusKind { Slow, DamageOverTime, Shield, Stun }
+
+/// Sent when a status is newly applied or stacked, refreshes are not logged
+#[derive(Event)]
+pub struct StatusAppliedEvent {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    pub ability: Option<AbilityType>,
+    pub status: StatusKind,
+    /// Seconds, 0 for statuses that last until removed such as permanent shields
+    pub duration: f32
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CombatLogEntry {
+    pub time: f32,
+    pub kind: CombatLogKind,
+    #[serde(skip)]
+    pub source_entity: Option<Entity>,
+    pub source: String,
+    pub target: String,
+    pub ability: String,
+    pub amount: f32,
+    pub crit: bool,
+    /// Status name, damage type or anything else that doesn't fit the other columns
+    pub detail: String
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat { Json, Csv }
+
+#[derive(Resource, Default)]
+pub struct CombatLog {
+    pub entries: VecDeque<CombatLogEntry>
+}
+
+impl CombatLog {
+    pub fn push(&mut self, entry: CombatLogEntry) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Damage dealt by `source` grouped by ability as `(ability, total, dps)`, highest total first.
+    /// DPS is measured from the first to the last hit so idle time before combat isn't counted
+    pub fn damage_by_ability(&self, source: Entity) -> Vec<(String, f32, f32)> {
+        let hits = self.entries.iter().filter(|entry| entry.kind == CombatLogKind::Damage && entry.source_entity == Some(source));
+        let mut totals: Vec<(String, f32)> = Vec::new();
+        let (mut start, mut end) = (f32::MAX, 0.0f32);
+        for hit in hits {
+            start = start.min(hit.time);
+            end = end.max(hit.time);
+            match totals.iter_mut().find(|(ability, _)| *ability == hit.ability) {
+                Some((_, total)) => *total += hit.amount,
+                None => totals.push((hit.ability.clone(), hit.amount)),
+            }
+        }
+        let duration = (end - start).max(1.0);
+        let mut result: Vec<(String, f32, f32)> = totals.into_iter().map(|(ability, total)| (ability, total, total / duration)).collect();
+        result.sort_by(|a, b| b.1.total_cmp(&a.1));
+        result
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time,kind,source,target,ability,amount,crit,detail\n");
+        for entry in self.entries.iter() {
+            csv.push_str(&format!(
+                "{:.3},{:?},{},{},{},{:.2},{},{}\n",
+                entry.time, entry.kind, csv_escape(&entry.source), csv_escape(&entry.target), csv_escape(&entry.ability), entry.amount, entry.crit, csv_escape(&entry.detail)
+            ));
+        }
+        csv
+    }
+
+    pub fn export(&self, format: ExportFormat) -> io::Result<PathBuf> {
+        fs::create_dir_all(EXPORT_DIR)?;
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        let (extension, contents) = match format {
+            ExportFormat::Json => ("json", serde_json::to_string_pretty(&self.entries).map_err(io::Error::from)?),
+            ExportFormat::Csv => ("csv", self.to_csv()),
+        };
+        let path = PathBuf::from(EXPORT_DIR).join(format!("combat_log_{}.{}", timestamp, extension));
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn entity_label(entity: Option<Entity>, names: &Query<&Name>) -> String {
+    match entity {
+        Some(entity) => names.get(entity).map(|name| name.to_string()).unwrap_or_else(|_| format!("{:?}", entity)),
+        None => String::new(),
+    }
+}
+
+fn ability_label(ability: Option<AbilityType>) -> String {
+    ability.map_or_else(|| String::from("Attack"), |ability| format!("{:?}", ability))
+}
+
+fn log_casts(time: Res<Time>, mut log: ResMut<CombatLog>, mut evr_cast: EventReader<AbilityCastEvent>, names: Query<&Name>) {
+    for cast_event in evr_cast.read() {
+        log.push(CombatLogEntry {
+            time: time.elapsed_seconds(),
+            kind: CombatLogKind::Cast,
+            source_entity: Some(cast_event.caster),
+            source: entity_label(Some(cast_event.caster), &names),
+            target: String::new(),
+            ability: ability_label(Some(cast_event.ability_type)),
+            amount: 0.0,
+            crit: false,
+            detail: String::new()
+        });
+    }
+}
+
+fn log_damage(time: Res<Time>, mut log: ResMut<CombatLog>, mut evr_damage: EventReader<HealthDamageEvent>, names: Query<&Name>) {
+    for damage_event in evr_damage.read() {
+        log.push(CombatLogEntry {
+            time: time.elapsed_seconds(),
+            kind: CombatLogKind::Damage,
+            source_entity: damage_event.source,
+            source: entity_label(damage_event.source, &names),
+            target: entity_label(Some(damage_event.entity), &names),
+            ability: ability_label(damage_event.ability),
+            amount: damage_event.amount,
+            crit: damage_event.report.crit,
+            detail: format!("{:?}{}", damage_event.report.damage_type, if damage_event.is_dot { " DoT" } else { "" })
+        });
+    }
+}
+
+fn log_heals(time: Res<Time>, mut log: ResMut<CombatLog>, mut evr_heal: EventReader<HealthHealEvent>, names: Query<&Name>) {
+    for heal_event in evr_heal.read() {
+        log.push(CombatLogEntry {
+            time: time.elapsed_seconds(),
+            kind: CombatLogKind::Heal,
+            source_entity: heal_event.source,
+            source: entity_label(heal_event.source, &names),
+            target: entity_label(Some(heal_event.entity), &names),
+            ability: heal_event.ability.map(|ability| format!("{:?}", ability)).unwrap_or_default(),
+            amount: heal_event.amount,
+            crit: false,
+            detail: String::new()
+        });
+    }
+}
+
+fn log_statuses(time: Res<Time>, mut log: ResMut<CombatLog>, mut evr_status: EventReader<StatusAppliedEvent>, names: Query<&Name>) {
+    for status_event in evr_status.read() {
+        log.push(CombatLogEntry {
+            time: time.elapsed_seconds(),
+            kind: CombatLogKind::Status,
+            source_entity: status_event.source,
+            source: entity_label(status_event.source, &names),
+            target: entity_label(Some(status_event.target), &names),
+            ability: status_event.ability.map(|ability| format!("{:?}", ability)).unwrap_or_default(),
+            amount: status_event.duration,
+            crit: false,
+            detail: format!("{:?}", status_event.status)
+        });
+    }
+}
+
+fn log_kills(time: Res<Time>, mut log: ResMut<CombatLog>, mut evr_death: EventReader<HealthDeathEvent>, names: Query<&Name>) {
+    for death_event in evr_death.read() {
+        log.push(CombatLogEntry {
+            time: time.elapsed_seconds(),
+            kind: CombatLogKind::Kill,
+            source_entity: death_event.killer,
+            source: entity_label(death_event.killer, &names),
+            target: entity_label(Some(death_event.entity), &names),
+            ability: String::new(),
+            amount: 0.0,
+            crit: false,
+            detail: format!("{:?}", death_event.entity_type)
+        });
+    }
+}
+
+/// F9 exports JSON, F10 exports CSV
+fn export_combat_log(input: Res<ButtonInput<KeyCode>>, log: Res<CombatLog>) {
+    let format = if input.just_pressed(KeyCode::F9) {
+        ExportFormat::Json
+    } else if input.just_pressed(KeyCode::F10) {
+        ExportFormat::Csv
+    } else {
+        return;
+    };
+    match log.export(format) {
+        Ok(path) => info!("Exported {} combat log entries to {}", log.entries.len(), path.display()),
+        Err(error) => error!("Failed to export combat log: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn damage(time: f32, source: Entity, ability: &str, amount: f32) -> CombatLogEntry {
+        CombatLogEntry {
+            time,
+            kind: CombatLogKind::Damage,
+            source_entity: Some(source),
+            source: String::from("Player"),
+            target: String::from("Orc"),
+            ability: String::from(ability),
+            amount,
+            crit: false,
+            detail: String::new()
+        }
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("Orc"), "Orc");
+        assert_eq!(csv_escape("Orc, Elite"), "\"Orc, Elite\"");
+        assert_eq!(csv_escape("The \"Boss\""), "\"The \"\"Boss\"\"\"");
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let mut log = CombatLog::default();
+        let mut entry = damage(1.5, Entity::from_raw(0), "Fireball", 12.0);
+        entry.target = String::from("Hasted, Vampiric Orc");
+        entry.crit = true;
+        entry.detail = String::from("Fire");
+        log.push(entry);
+        let csv = log.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "time,kind,source,target,ability,amount,crit,detail");
+        assert_eq!(lines[1], "1.500,Damage,Player,\"Hasted, Vampiric Orc\",Fireball,12.00,true,Fire");
+        assert_eq!(lines.len(), 2);
+    }
+
+    #[test]
+    fn test_damage_by_ability() {
+        let (player, other) = (Entity::from_raw(0), Entity::from_raw(1));
+        let mut log = CombatLog::default();
+        log.push(damage(0.0, player, "Attack", 10.0));
+        log.push(damage(2.0, player, "Fireball", 30.0));
+        log.push(damage(4.0, player, "Attack", 10.0));
+        log.push(damage(3.0, other, "Attack", 100.0));
+        let rows = log.damage_by_ability(player);
+        assert_eq!(rows, vec![(String::from("Fireball"), 30.0, 7.5), (String::from("Attack"), 20.0, 5.0)]);
+        // NOTE: A single hit is spread over at least a second
+        let mut single = CombatLog::default();
+        single.push(damage(5.0, player, "Attack", 8.0));
+        assert_eq!(single.damage_by_ability(player), vec![(String::from("Attack"), 8.0, 8.0)]);
+        assert!(single.damage_by_ability(other).is_empty());
+    }
+}
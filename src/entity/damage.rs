@@ -1,12 +1,174 @@
 use bevy::prelude::Reflect;
 
-#[derive(Clone, Copy, Reflect, Debug)]
+use super::stats::{Stats, StatType};
+
+#[derive(Clone, Copy, Reflect, Debug, PartialEq, Eq)]
 pub enum DamageType { PHYSICAL, MAGICAL, BYPASS }
 
-pub fn multiplier_from_defence(defence: i32) -> f32 {
-    if defence > 0 {
-        return 100.0 / 100.0 + defence as f32;
+/// Attacker stats captured when the damage was created, so later stat changes don't affect it
+#[derive(Clone, Copy, Reflect, Debug, Default)]
+pub struct AttackerSnapshot {
+    pub attack: f32,
+    pub magic: f32,
+    pub crit_chance: f32,
+    pub crit_damage: f32,
+    pub armour_penetration: f32,
+    pub magic_penetration: f32
+}
+
+impl AttackerSnapshot {
+    pub fn from_stats(stats: &Stats) -> Self {
+        let get = |stat_type: StatType| stats.get_stat(stat_type).copied().unwrap_or(0.0);
+        AttackerSnapshot {
+            attack: get(StatType::Attack),
+            magic: get(StatType::Magic),
+            crit_chance: get(StatType::CritChance),
+            crit_damage: get(StatType::CritDamage),
+            armour_penetration: get(StatType::ArmourPenetration),
+            magic_penetration: get(StatType::MagicPenetration),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Reflect, Debug, Default)]
+pub struct DefenderSnapshot {
+    pub physical_defence: f32,
+    pub magical_defence: f32,
+    pub damage_reduction: f32
+}
+
+/// Every step of a single damage calculation, in the order it was applied
+#[derive(Clone, Copy, Reflect, Debug)]
+pub struct DamageReport {
+    pub damage_type: DamageType,
+    pub base_amount: f32,
+    pub scaled_amount: f32,
+    pub crit: bool,
+    pub crit_multiplier: f32,
+    pub defence: f32,
+    pub penetration: f32,
+    pub resistance_multiplier: f32,
+    pub flat_reduction: f32,
+    /// Taken by shields after mitigation, see `health::Shield`
+    pub absorbed: f32,
+    pub reflected: f32,
+    pub final_amount: f32
+}
+
+/// `crit_roll` is expected to be uniform in `[0, 1)`, a crit happens when it is below the crit chance
+pub fn calculate_damage(
+    base_amount: f32,
+    damage_type: DamageType,
+    attacker: Option<&AttackerSnapshot>,
+    defender: &DefenderSnapshot,
+    crit_roll: f32
+) -> DamageReport {
+    let base_amount = base_amount.max(0.0);
+    let mut report = DamageReport {
+        damage_type,
+        base_amount,
+        scaled_amount: base_amount,
+        crit: false,
+        crit_multiplier: 1.0,
+        defence: 0.0,
+        penetration: 0.0,
+        resistance_multiplier: 1.0,
+        flat_reduction: 0.0,
+        absorbed: 0.0,
+        reflected: 0.0,
+        final_amount: base_amount
+    };
+    if damage_type == DamageType::BYPASS {
+        return report;
+    }
+
+    if let Some(attacker) = attacker {
+        let (scaling, penetration) = match damage_type {
+            DamageType::PHYSICAL => (attacker.attack, attacker.armour_penetration),
+            _ => (attacker.magic, attacker.magic_penetration),
+        };
+        report.scaled_amount = base_amount * (1.0 + scaling.max(0.0) / 100.0);
+        report.penetration = penetration.max(0.0);
+        if crit_roll < attacker.crit_chance {
+            report.crit = true;
+            report.crit_multiplier = attacker.crit_damage.max(1.0);
+        }
+    }
+
+    report.defence = match damage_type {
+        DamageType::PHYSICAL => defender.physical_defence,
+        _ => defender.magical_defence,
+    };
+    // NOTE: Penetration can remove defence but never pushes it negative, negative defence comes from debuffs only
+    let effective_defence = if report.defence > 0.0 { (report.defence - report.penetration).max(0.0) } else { report.defence };
+    report.resistance_multiplier = multiplier_from_defence(effective_defence);
+    report.flat_reduction = defender.damage_reduction.max(0.0);
+    report.final_amount = (report.scaled_amount * report.crit_multiplier * report.resistance_multiplier - report.flat_reduction).max(0.0);
+    report
+}
+
+/// Positive defence gives diminishing reduction (100 defence halves damage), negative defence amplifies damage up to double
+pub fn multiplier_from_defence(defence: f32) -> f32 {
+    if defence >= 0.0 {
+        100.0 / (100.0 + defence)
     } else {
-        return 2.0 - (100.0 / (100.0 - defence as f32));
+        2.0 - (100.0 / (100.0 - defence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 0.0001;
+
+    fn attacker() -> AttackerSnapshot {
+        AttackerSnapshot { attack: 50.0, magic: 100.0, crit_chance: 0.25, crit_damage: 2.0, armour_penetration: 0.0, magic_penetration: 0.0 }
+    }
+
+    #[test]
+    pub fn test_defence_multiplier() {
+        assert!((multiplier_from_defence(0.0) - 1.0).abs() < EPSILON);
+        assert!((multiplier_from_defence(100.0) - 0.5).abs() < EPSILON);
+        assert!((multiplier_from_defence(-100.0) - 1.5).abs() < EPSILON);
+        assert!(multiplier_from_defence(25.0) < 1.0, "Positive defence must reduce damage");
+    }
+
+    #[test]
+    pub fn test_stat_scaling() {
+        let defender = DefenderSnapshot::default();
+        let physical = calculate_damage(10.0, DamageType::PHYSICAL, Some(&attacker()), &defender, 1.0);
+        let magical = calculate_damage(10.0, DamageType::MAGICAL, Some(&attacker()), &defender, 1.0);
+        assert!((physical.final_amount - 15.0).abs() < EPSILON, "{:?}", physical);
+        assert!((magical.final_amount - 20.0).abs() < EPSILON, "{:?}", magical);
+    }
+
+    #[test]
+    pub fn test_crit() {
+        let defender = DefenderSnapshot::default();
+        let crit = calculate_damage(10.0, DamageType::PHYSICAL, Some(&attacker()), &defender, 0.1);
+        let no_crit = calculate_damage(10.0, DamageType::PHYSICAL, Some(&attacker()), &defender, 0.5);
+        assert!(crit.crit && !no_crit.crit);
+        assert!((crit.final_amount - 30.0).abs() < EPSILON, "{:?}", crit);
+    }
+
+    #[test]
+    pub fn test_penetration_and_reduction() {
+        let defender = DefenderSnapshot { physical_defence: 100.0, magical_defence: 0.0, damage_reduction: 2.0 };
+        let mut pen_attacker = attacker();
+        pen_attacker.armour_penetration = 200.0;
+        let report = calculate_damage(10.0, DamageType::PHYSICAL, Some(&pen_attacker), &defender, 1.0);
+        assert!((report.resistance_multiplier - 1.0).abs() < EPSILON, "Penetration should not go below zero defence");
+        assert!((report.final_amount - 13.0).abs() < EPSILON, "{:?}", report);
+        let blocked = calculate_damage(1.0, DamageType::PHYSICAL, None, &defender, 1.0);
+        assert_eq!(blocked.final_amount, 0.0);
+    }
+
+    #[test]
+    pub fn test_bypass() {
+        let defender = DefenderSnapshot { physical_defence: 100.0, magical_defence: 100.0, damage_reduction: 5.0 };
+        let report = calculate_damage(10.0, DamageType::BYPASS, Some(&attacker()), &defender, 0.0);
+        assert_eq!(report.final_amount, 10.0);
+        assert!(!report.crit);
     }
 }
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::abilities::abilities::AutoDestroy;
+use crate::entity::{damage::{AttackerSnapshot, DamageType}, health::{DamageInstance, Health}, hit_reaction::Stunned, stats::{Stats, StatType}};
+use crate::player::Player;
+
+use super::orc::get_player_pos;
+
+const ARROW_LIFETIME: f32 = 3.0;
+const ARROW_RADIUS: f32 = 4.0;
+const ARROW_KNOCKBACK: f32 = 60.0;
+
+pub struct ArcherPlugin;
+
+impl Plugin for ArcherPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Kite>()
+           .add_systems(Update, (kite_update, enemy_projectile_hits));
+    }
+}
+
+#[derive(Component, Reflect, Debug, Clone)]
+pub struct Kite;
+
+/// Fired by ranged enemies, only hurts the player and is destroyed on anything solid
+#[derive(Component)]
+pub struct EnemyProjectile {
+    pub damage: f32,
+    pub knockback: f32,
+    pub attacker: AttackerSnapshot,
+    pub owner: Entity
+}
+
+pub fn spawn_arrow(commands: &mut Commands, owner: Entity, origin: Vec2, direction: Vec2, speed: f32, damage: f32, attacker: AttackerSnapshot) -> Entity {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite { color: Color::rgb(0.9, 0.8, 0.5), custom_size: Some(Vec2::new(ARROW_RADIUS * 4.0, ARROW_RADIUS)), ..default() },
+            transform: Transform::from_translation(origin.extend(-1.0)).with_rotation(Quat::from_rotation_z(Vec2::X.angle_between(direction))),
+            ..default()
+        },
+        EnemyProjectile { damage, knockback: ARROW_KNOCKBACK, attacker, owner },
+        RigidBody::KinematicVelocityBased,
+        Collider::ball(ARROW_RADIUS),
+        Sensor,
+        Velocity { linvel: direction * speed, angvel: 0.0 },
+        AutoDestroy::new(ARROW_LIFETIME),
+        Name::new("Arrow")
+    )).id()
+}
+
+/// Backs straight away from the player, the definition decides when to stop
+fn kite_update(
+    player_query: Query<&Transform, With<Player>>,
+    mut archers: Query<(&Transform, &Stats, &mut Velocity), (With<Kite>, Without<Stunned>)>
+) {
+    let Some(player_pos) = get_player_pos(player_query.get_single()) else { return; };
+    for (transform, stats, mut velocity) in archers.iter_mut() {
+        let speed = *stats.get_stat(StatType::Speed).unwrap_or(&0.0);
+        velocity.linvel = (transform.translation.truncate() - player_pos).normalize_or_zero() * speed;
+    }
+}
+
+fn enemy_projectile_hits(
+    mut commands: Commands,
+    rapier: Res<RapierContext>,
+    mut player_query: Query<(Entity, &mut Health), With<Player>>,
+    projectiles: Query<(Entity, &EnemyProjectile, &Transform, &Velocity)>
+) {
+    let player = player_query.get_single_mut().ok();
+    let (player_entity, mut player_health) = match player {
+        Some((entity, health)) => (Some(entity), Some(health)),
+        None => (None, None),
+    };
+    for (entity, projectile, transform, velocity) in projectiles.iter() {
+        let direction = velocity.linvel.normalize_or_zero();
+        if let (Some(player_entity), Some(player_health)) = (player_entity, player_health.as_mut()) {
+            if rapier.intersection_pair(player_entity, entity).is_some() {
+                player_health.push(
+                    DamageInstance::new(projectile.damage, DamageType::PHYSICAL, true)
+                        .with_attacker(projectile.attacker)
+                        .with_source(projectile.owner)
+                        .with_knockback(direction * projectile.knockback)
+                );
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        }
+        // NOTE: Enemies are sensors so excluding sensors leaves walls and the player as the only things that stop arrows
+        let filter = QueryFilter::default().exclude_sensors().exclude_collider(entity);
+        if rapier.cast_ray(transform.translation.truncate(), direction, ARROW_RADIUS, true, filter).is_some() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,663 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::abilities::abilities::AbilitySystem;
+use crate::animation::directional_animator::{AnimationType, DirectionalAnimator};
+use crate::entity::{health::Health, hit_reaction::Stunned, stats::{Stats, StatType}};
+use crate::pathfinding::{AIPath, AITarget, Grid, GRID_TOLERANCE};
+use crate::player::Player;
+
+use super::orc::{pick_wander_destination, update_chase_destination, Attack};
+use super::manager::EnemyManager;
+use super::perception::Perception;
+use super::tactics::{may_attack, AttackSlot, Coordinated};
+use super::territory::Territory;
+use super::state_machine::{has_line_of_sight, Distance, Guard, GuardContext};
+use super::Enemy;
+
+/// Gives up on a wander destination that can't be reached after this long
+const WANDER_TIMEOUT: f32 = 8.0;
+
+pub struct BehaviourTreePlugin;
+
+impl Plugin for BehaviourTreePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<BehaviourTree>()
+           .add_systems(Update, tick_behaviour_trees);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum NodeStatus { Success, Failure, Running }
+
+/// A node in a `BehaviourTree`, children are indices into the tree's node list
+#[derive(Debug, Clone, Reflect, Deserialize)]
+pub enum BehaviourNode {
+    /// Runs children in order until one fails, carrying on from a running child next tick
+    Sequence(Vec<usize>),
+    /// Runs the first child that doesn't fail, checked from the start every tick so higher priorities can interrupt
+    Selector(Vec<usize>),
+    /// Tries children from highest to lowest score until one doesn't fail
+    Utility(Vec<UtilityOption>),
+    Inverter(usize),
+    /// Turns a failure into a success
+    Succeeder(usize),
+    /// Fails for `duration` seconds after the child last succeeded
+    Cooldown { child: usize, duration: f32 },
+    Condition(Guard),
+    Action(BehaviourAction),
+}
+
+#[derive(Debug, Clone, Reflect, Deserialize)]
+pub struct UtilityOption {
+    pub child: usize,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// Each in `[0, 1]` and multiplied with `weight`
+    #[serde(default)]
+    pub considerations: Vec<Consideration>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, Reflect, Deserialize)]
+pub enum Consideration {
+    /// Missing health percentage
+    LowHealth,
+    /// 1 next to the player falling to 0 at the distance
+    PlayerNear(Distance),
+    /// Largest missing health percentage of an ally within this range
+    AllyInjured(f32),
+    /// 1 if the guard passes, 0 otherwise
+    Guard(Guard),
+    Constant(f32),
+}
+
+/// Leaf behaviour, driving the same `AITarget`, animator and attack systems as the state machine
+#[derive(Debug, Clone, Copy, Reflect, Deserialize)]
+pub enum BehaviourAction {
+    /// Paths toward the player, succeeding once in attack range
+    MoveToPlayer,
+    /// Paths to a random nearby point
+    Wander,
+    /// Backs away from the player until this far away
+    Retreat(f32),
+    Wait(f32),
+    /// Attacks through the `Attack` state, succeeding after this many seconds of recovery
+    Attack(f32),
+    /// Heals the most injured ally in range, fails if nobody needs it
+    HealAlly { amount: f32, range: f32 },
+    PlayAnimation(AnimationType),
+}
+
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct NodeState {
+    /// Result of the last tick, for watching the tree in the inspector
+    pub status: Option<NodeStatus>,
+    running_child: Option<usize>,
+    timer: f32,
+    started: bool,
+    ready_at: f32,
+}
+
+/// Changes to other components and entities, queued so ticking a tree doesn't need `Commands`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TreeCommand {
+    ClearPath,
+    StartAttack,
+    StopAttack,
+    Heal { target: Entity, amount: f32 },
+}
+
+/// Alternative to `StateMachine` for archetypes that need priorities or scoring, node 0 is the root
+#[derive(Component, Reflect)]
+pub struct BehaviourTree {
+    pub nodes: Vec<BehaviourNode>,
+    pub states: Vec<NodeState>,
+    elapsed: f32,
+    /// The action left running by the last tick and when it started, `MinTimeInState` guards measure from this
+    active: Option<usize>,
+    active_since: f32,
+}
+
+impl BehaviourTree {
+    pub fn new(nodes: Vec<BehaviourNode>) -> Self {
+        BehaviourTree { states: vec![NodeState::default(); nodes.len()], nodes, elapsed: 0.0, active: None, active_since: 0.0 }
+    }
+
+    fn update(&mut self, context: &mut TreeContext) -> NodeStatus {
+        self.elapsed += context.delta;
+        context.elapsed = self.elapsed;
+        context.guards.time_in_state = self.elapsed - self.active_since;
+        context.running = None;
+        let status = tick(&self.nodes, &mut self.states, 0, context);
+        if context.running != self.active {
+            self.active = context.running;
+            self.active_since = self.elapsed;
+        }
+        status
+    }
+}
+
+struct TreeContext<'a> {
+    entity: Entity,
+    delta: f32,
+    /// Filled in by `BehaviourTree::update`
+    elapsed: f32,
+    position: Vec2,
+    player: Option<Vec2>,
+    speed: f32,
+    guards: GuardContext,
+    line_of_sight: &'a mut dyn FnMut() -> bool,
+    enemies: &'a EnemyManager,
+    /// Health percentage of every living enemy
+    health_percents: &'a HashMap<Entity, f32>,
+    grid: &'a Grid,
+    ai: &'a mut AITarget,
+    animator: &'a mut DirectionalAnimator,
+    velocity: &'a mut Velocity,
+    /// The action node that returned `Running` this tick
+    running: Option<usize>,
+    commands: Vec<TreeCommand>,
+}
+
+impl Consideration {
+    fn score(&self, context: &mut TreeContext) -> f32 {
+        match self {
+            Consideration::LowHealth => 1.0 - context.guards.health_percent,
+            Consideration::PlayerNear(distance) => {
+                let range = match distance {
+                    Distance::AttackRange => context.guards.attack_range,
+                    Distance::FollowRange => context.guards.follow_range,
+                    Distance::Fixed(range) => *range,
+                };
+                context.guards.player_distance.map_or(0.0, |player_distance| 1.0 - (player_distance / range.max(f32::EPSILON)).clamp(0.0, 1.0))
+            },
+            Consideration::AllyInjured(range) => context.enemies.within_radius(context.position, *range).into_iter()
+                .filter(|ally| *ally != context.entity)
+                .filter_map(|ally| context.health_percents.get(&ally))
+                .map(|percent| 1.0 - percent)
+                .fold(0.0, f32::max),
+            Consideration::Guard(guard) => if guard.passes(&context.guards, &mut context.line_of_sight) { 1.0 } else { 0.0 },
+            Consideration::Constant(value) => *value,
+        }
+    }
+}
+
+fn tick(nodes: &[BehaviourNode], states: &mut [NodeState], index: usize, context: &mut TreeContext) -> NodeStatus {
+    let Some(node) = nodes.get(index) else {
+        warn!("Behaviour tree node {} does not exist", index);
+        return NodeStatus::Failure;
+    };
+    let status = match node {
+        BehaviourNode::Sequence(children) => {
+            let start = states[index].running_child.take().unwrap_or(0);
+            let mut result = NodeStatus::Success;
+            for (position, child) in children.iter().enumerate().skip(start) {
+                result = tick(nodes, states, *child, context);
+                if result == NodeStatus::Running {
+                    states[index].running_child = Some(position);
+                }
+                if result != NodeStatus::Success {
+                    break;
+                }
+            }
+            result
+        },
+        BehaviourNode::Selector(children) => {
+            let previous = states[index].running_child.take();
+            let mut result = NodeStatus::Failure;
+            for (position, child) in children.iter().enumerate() {
+                result = tick(nodes, states, *child, context);
+                if result == NodeStatus::Failure {
+                    continue;
+                }
+                if let Some(previous) = previous.filter(|previous| *previous != position) {
+                    abort(nodes, states, children[previous], context);
+                }
+                if result == NodeStatus::Running {
+                    states[index].running_child = Some(position);
+                }
+                break;
+            }
+            result
+        },
+        BehaviourNode::Utility(options) => {
+            let previous = states[index].running_child.take();
+            let mut scored: Vec<(usize, f32)> = options.iter().enumerate()
+                .map(|(position, option)| (position, option.considerations.iter().fold(option.weight, |score, consideration| score * consideration.score(context))))
+                .filter(|(_, score)| *score > 0.0)
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let mut result = NodeStatus::Failure;
+            for (position, _) in scored {
+                result = tick(nodes, states, options[position].child, context);
+                if result == NodeStatus::Failure {
+                    continue;
+                }
+                if let Some(previous) = previous.filter(|previous| *previous != position) {
+                    abort(nodes, states, options[previous].child, context);
+                }
+                if result == NodeStatus::Running {
+                    states[index].running_child = Some(position);
+                }
+                break;
+            }
+            if let Some(previous) = previous.filter(|_| result == NodeStatus::Failure) {
+                abort(nodes, states, options[previous].child, context);
+            }
+            result
+        },
+        BehaviourNode::Inverter(child) => match tick(nodes, states, *child, context) {
+            NodeStatus::Success => NodeStatus::Failure,
+            NodeStatus::Failure => NodeStatus::Success,
+            NodeStatus::Running => NodeStatus::Running,
+        },
+        BehaviourNode::Succeeder(child) => match tick(nodes, states, *child, context) {
+            NodeStatus::Running => NodeStatus::Running,
+            _ => NodeStatus::Success,
+        },
+        BehaviourNode::Cooldown { child, duration } => {
+            if context.elapsed < states[index].ready_at {
+                NodeStatus::Failure
+            } else {
+                let result = tick(nodes, states, *child, context);
+                if result == NodeStatus::Success {
+                    states[index].ready_at = context.elapsed + duration;
+                }
+                result
+            }
+        },
+        BehaviourNode::Condition(guard) => if guard.passes(&context.guards, &mut context.line_of_sight) { NodeStatus::Success } else { NodeStatus::Failure },
+        BehaviourNode::Action(action) => {
+            let result = run_action(action, &mut states[index], context);
+            if result == NodeStatus::Running {
+                context.running = Some(index);
+            }
+            result
+        },
+    };
+    states[index].status = Some(status);
+    status
+}
+
+/// Resets a branch that was interrupted while running, undoing anything an action left behind
+fn abort(nodes: &[BehaviourNode], states: &mut [NodeState], index: usize, context: &mut TreeContext) {
+    let Some(node) = nodes.get(index) else { return; };
+    match node {
+        BehaviourNode::Sequence(children) | BehaviourNode::Selector(children) => {
+            for child in children {
+                abort(nodes, states, *child, context);
+            }
+        },
+        BehaviourNode::Utility(options) => {
+            for option in options {
+                abort(nodes, states, option.child, context);
+            }
+        },
+        BehaviourNode::Inverter(child) | BehaviourNode::Succeeder(child) | BehaviourNode::Cooldown { child, .. } => abort(nodes, states, *child, context),
+        BehaviourNode::Action(BehaviourAction::Attack(_)) if states[index].started => {
+            context.commands.push(TreeCommand::StopAttack);
+        },
+        BehaviourNode::Action(BehaviourAction::Retreat(_)) if states[index].started => {
+            context.velocity.linvel = Vec2::ZERO;
+        },
+        _ => {}
+    }
+    let state = &mut states[index];
+    state.running_child = None;
+    state.timer = 0.0;
+    state.started = false;
+}
+
+fn stop_moving(context: &mut TreeContext) {
+    context.ai.do_path_find = false;
+    context.velocity.linvel = Vec2::ZERO;
+    context.commands.push(TreeCommand::ClearPath);
+}
+
+fn run_action(action: &BehaviourAction, state: &mut NodeState, context: &mut TreeContext) -> NodeStatus {
+    let first_tick = !state.started;
+    state.started = true;
+    state.timer += context.delta;
+    let status = match action {
+        BehaviourAction::MoveToPlayer => match context.player {
+            None => NodeStatus::Failure,
+            Some(player_pos) => {
+                if first_tick {
+                    context.ai.do_path_find = true;
+                    context.animator.update_animation(AnimationType::Walk);
+                }
+                if context.position.distance(player_pos) <= context.ai.attack_range {
+                    NodeStatus::Success
+                } else {
+                    if update_chase_destination(context.grid, player_pos, context.ai) {
+                        context.commands.push(TreeCommand::ClearPath);
+                    }
+                    NodeStatus::Running
+                }
+            }
+        },
+        BehaviourAction::Wander => {
+            if first_tick {
+                let Some(destination) = pick_wander_destination(context.grid, context.position) else { return finish(state, NodeStatus::Failure); };
+                context.ai.destination = destination;
+                context.ai.do_path_find = true;
+                context.commands.push(TreeCommand::ClearPath);
+                context.animator.update_animation(AnimationType::Walk);
+            }
+            let at_destination = context.position.distance_squared(context.grid.grid_to_world(context.ai.destination)) <= GRID_TOLERANCE.powi(2);
+            if (at_destination && state.timer >= 0.5) || state.timer >= WANDER_TIMEOUT { NodeStatus::Success } else { NodeStatus::Running }
+        },
+        BehaviourAction::Retreat(distance) => match context.player {
+            None => NodeStatus::Failure,
+            Some(player_pos) => {
+                if first_tick {
+                    stop_moving(context);
+                    context.animator.update_animation(AnimationType::Walk);
+                }
+                if context.position.distance(player_pos) >= *distance {
+                    context.velocity.linvel = Vec2::ZERO;
+                    NodeStatus::Success
+                } else {
+                    context.velocity.linvel = (context.position - player_pos).normalize_or_zero() * context.speed;
+                    NodeStatus::Running
+                }
+            }
+        },
+        BehaviourAction::Wait(duration) => {
+            if first_tick {
+                stop_moving(context);
+                context.animator.update_animation(AnimationType::Idle);
+            }
+            if state.timer >= *duration { NodeStatus::Success } else { NodeStatus::Running }
+        },
+        BehaviourAction::Attack(recovery) => {
+            if first_tick {
+                if context.player.is_none() {
+                    return finish(state, NodeStatus::Failure);
+                }
+                stop_moving(context);
+                context.animator.update_animation(AnimationType::Attack);
+                context.commands.push(TreeCommand::StartAttack);
+            }
+            if state.timer >= *recovery {
+                context.commands.push(TreeCommand::StopAttack);
+                NodeStatus::Success
+            } else {
+                NodeStatus::Running
+            }
+        },
+        BehaviourAction::HealAlly { amount, range } => {
+            let target = context.enemies.within_radius(context.position, *range).into_iter()
+                .filter(|ally| *ally != context.entity)
+                .filter_map(|ally| context.health_percents.get(&ally).map(|percent| (ally, *percent)))
+                .filter(|(_, percent)| *percent < 1.0)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match target {
+                Some((ally, _)) => {
+                    context.commands.push(TreeCommand::Heal { target: ally, amount: *amount });
+                    context.animator.update_animation(AnimationType::Attack);
+                    NodeStatus::Success
+                },
+                None => NodeStatus::Failure,
+            }
+        },
+        BehaviourAction::PlayAnimation(animation) => {
+            context.animator.update_animation(*animation);
+            NodeStatus::Success
+        },
+    };
+    finish(state, status)
+}
+
+fn finish(state: &mut NodeState, status: NodeStatus) -> NodeStatus {
+    if status != NodeStatus::Running {
+        state.started = false;
+        state.timer = 0.0;
+    }
+    status
+}
+
+pub fn tick_behaviour_trees(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    rapier: Res<RapierContext>,
+    enemies: Res<EnemyManager>,
+    mut commands: Commands,
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    mut healths: ParamSet<(Query<(Entity, &Health), With<Enemy>>, Query<&mut Health>)>,
+    mut trees: Query<(Entity, &mut BehaviourTree, &Transform, &Stats, &mut AITarget, &mut DirectionalAnimator, &mut Velocity, Option<&AbilitySystem>, Option<&Perception>, Option<&AttackSlot>, Has<Coordinated>, Option<&Territory>), (Without<Player>, Without<Stunned>)>
+) {
+    let player = player_query.get_single().ok().map(|(entity, transform)| (entity, transform.translation.truncate()));
+    let health_percents: HashMap<Entity, f32> = healths.p0().iter()
+        .filter(|(_, health)| !health.is_dead())
+        .map(|(entity, health)| (entity, health.get_percent()))
+        .collect();
+    let mut heals = Vec::new();
+    for (entity, mut tree, transform, stats, mut ai, mut animator, mut velocity, abilities, perception, slot, coordinated, territory) in trees.iter_mut() {
+        if tree.nodes.is_empty() {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let Some(health_percent) = health_percents.get(&entity).copied() else { continue; };
+        let mut guards = GuardContext {
+            player_distance: player.map(|(_, player_pos)| position.distance(player_pos)),
+            attack_range: ai.attack_range,
+            follow_range: ai.follow_range,
+            health_percent,
+            ability_ready: abilities.is_some_and(|abilities| abilities.abilities.iter().any(|ability| ability.can_use())),
+            at_destination: position.distance_squared(grid.grid_to_world(ai.destination)) <= GRID_TOLERANCE.powi(2),
+            time_in_state: 0.0,
+            can_see_player: false,
+            time_since_seen: 0.0,
+            heard_noise: false,
+            may_attack: may_attack(slot, coordinated),
+            home_distance: None,
+            player_home_distance: None,
+            leash_radius: f32::INFINITY
+        };
+        guards.perceive(perception);
+        guards.territory(territory, position, player.map(|(_, player_pos)| player_pos));
+        let mut line_of_sight_cache: Option<bool> = None;
+        let mut line_of_sight = || *line_of_sight_cache.get_or_insert_with(|| {
+            player.is_some_and(|player| has_line_of_sight(&rapier, entity, position, player))
+        });
+        let mut context = TreeContext {
+            entity,
+            delta: time.delta_seconds(),
+            elapsed: 0.0,
+            position,
+            player: player.map(|(_, player_pos)| player_pos),
+            speed: *stats.get_stat(StatType::Speed).unwrap_or(&0.0),
+            guards,
+            line_of_sight: &mut line_of_sight,
+            enemies: &enemies,
+            health_percents: &health_percents,
+            grid: &grid,
+            ai: &mut *ai,
+            animator: &mut *animator,
+            velocity: &mut *velocity,
+            running: None,
+            commands: Vec::new(),
+        };
+        tree.update(&mut context);
+        for command in context.commands {
+            match command {
+                TreeCommand::ClearPath => { commands.entity(entity).remove::<AIPath>(); },
+                TreeCommand::StartAttack => { commands.entity(entity).insert(Attack); },
+                TreeCommand::StopAttack => { commands.entity(entity).remove::<Attack>(); },
+                TreeCommand::Heal { target, amount } => heals.push((target, amount, entity)),
+            }
+        }
+    }
+    let mut health_query = healths.p1();
+    for (target, amount, source) in heals {
+        if let Ok(mut health) = health_query.get_mut(target) {
+            health.heal_from(amount, Some(source), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animation::directional_animator::AnimationDirection;
+
+    use super::*;
+    use super::super::EnemyType;
+
+    /// Everything a tree drives, standing in for the enemy's components
+    struct Harness {
+        grid: Grid,
+        ai: AITarget,
+        animator: DirectionalAnimator,
+        velocity: Velocity,
+        health_percent: f32,
+        player: Option<Vec2>,
+        enemies: EnemyManager,
+        health_percents: HashMap<Entity, f32>,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Harness {
+                grid: Grid::default(),
+                ai: AITarget::new(256.0, 16.0, false),
+                animator: DirectionalAnimator { animation_indices: Default::default(), animation: AnimationType::Idle, direction: AnimationDirection::Down, last_update_timer: 0.0 },
+                velocity: Velocity::zero(),
+                health_percent: 1.0,
+                player: Some(Vec2::new(10.0, 0.0)),
+                enemies: EnemyManager::default(),
+                health_percents: HashMap::new(),
+            }
+        }
+
+        fn update(&mut self, tree: &mut BehaviourTree, delta: f32) -> (NodeStatus, Vec<TreeCommand>) {
+            let mut guards = GuardContext { player_distance: self.player.map(|player| player.length()), attack_range: self.ai.attack_range, follow_range: self.ai.follow_range, health_percent: self.health_percent, ability_ready: false, at_destination: false, time_in_state: 0.0, can_see_player: false, time_since_seen: 0.0, heard_noise: false, may_attack: true, home_distance: None, player_home_distance: None, leash_radius: f32::INFINITY };
+            guards.perceive(None);
+            let mut line_of_sight = || true;
+            let mut context = TreeContext {
+                entity: Entity::from_raw(0),
+                delta,
+                elapsed: 0.0,
+                position: Vec2::ZERO,
+                player: self.player,
+                speed: 50.0,
+                guards,
+                line_of_sight: &mut line_of_sight,
+                enemies: &self.enemies,
+                health_percents: &self.health_percents,
+                grid: &self.grid,
+                ai: &mut self.ai,
+                animator: &mut self.animator,
+                velocity: &mut self.velocity,
+                running: None,
+                commands: Vec::new(),
+            };
+            let status = tree.update(&mut context);
+            (status, context.commands)
+        }
+    }
+
+    #[test]
+    fn test_sequence_resumes_running_child() {
+        // NOTE: The cooldown would fail if the sequence started over instead of resuming at the wait
+        let mut tree = BehaviourTree::new(vec![
+            BehaviourNode::Sequence(vec![1, 3]),
+            BehaviourNode::Cooldown { child: 2, duration: 10.0 },
+            BehaviourNode::Action(BehaviourAction::Wait(0.5)),
+            BehaviourNode::Action(BehaviourAction::Wait(1.0)),
+        ]);
+        let mut harness = Harness::new();
+        assert_eq!(harness.update(&mut tree, 0.6).0, NodeStatus::Running);
+        assert_eq!(harness.update(&mut tree, 0.6).0, NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_selector_interrupt_aborts_running_branch() {
+        let mut tree = BehaviourTree::new(vec![
+            BehaviourNode::Selector(vec![1, 2]),
+            BehaviourNode::Sequence(vec![3, 4]),
+            BehaviourNode::Action(BehaviourAction::Attack(5.0)),
+            BehaviourNode::Condition(Guard::HealthBelow(0.5)),
+            BehaviourNode::Action(BehaviourAction::Retreat(100.0)),
+        ]);
+        let mut harness = Harness::new();
+        let (status, commands) = harness.update(&mut tree, 0.1);
+        assert_eq!(status, NodeStatus::Running);
+        assert!(commands.contains(&TreeCommand::StartAttack));
+        harness.health_percent = 0.3;
+        let (status, commands) = harness.update(&mut tree, 0.1);
+        assert_eq!(status, NodeStatus::Running);
+        assert!(commands.contains(&TreeCommand::StopAttack));
+        assert!(!tree.states[2].started);
+        assert!(harness.velocity.linvel.x < 0.0);
+    }
+
+    #[test]
+    fn test_utility_picks_highest_score() {
+        let mut tree = BehaviourTree::new(vec![
+            BehaviourNode::Utility(vec![
+                UtilityOption { child: 1, weight: 1.0, considerations: vec![Consideration::LowHealth] },
+                UtilityOption { child: 2, weight: 0.5, considerations: Vec::new() },
+            ]),
+            BehaviourNode::Action(BehaviourAction::PlayAnimation(AnimationType::Walk)),
+            BehaviourNode::Action(BehaviourAction::PlayAnimation(AnimationType::Run)),
+        ]);
+        let mut harness = Harness::new();
+        harness.health_percent = 0.9;
+        harness.update(&mut tree, 0.1);
+        assert_eq!(harness.animator.animation, AnimationType::Run);
+        harness.health_percent = 0.2;
+        harness.update(&mut tree, 0.1);
+        assert_eq!(harness.animator.animation, AnimationType::Walk);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let mut tree = BehaviourTree::new(vec![
+            BehaviourNode::Cooldown { child: 1, duration: 2.0 },
+            BehaviourNode::Action(BehaviourAction::PlayAnimation(AnimationType::Walk)),
+        ]);
+        let mut harness = Harness::new();
+        assert_eq!(harness.update(&mut tree, 0.5).0, NodeStatus::Success);
+        assert_eq!(harness.update(&mut tree, 0.5).0, NodeStatus::Failure);
+        assert_eq!(harness.update(&mut tree, 2.0).0, NodeStatus::Success);
+    }
+
+    #[test]
+    fn test_time_in_state_follows_running_action() {
+        let mut tree = BehaviourTree::new(vec![
+            BehaviourNode::Selector(vec![1, 2]),
+            BehaviourNode::Sequence(vec![3, 4]),
+            BehaviourNode::Action(BehaviourAction::Wait(10.0)),
+            BehaviourNode::Condition(Guard::MinTimeInState(1.0)),
+            BehaviourNode::Action(BehaviourAction::PlayAnimation(AnimationType::Run)),
+        ]);
+        let mut harness = Harness::new();
+        // NOTE: The first tick has nothing running yet so it counts from the start of the tree
+        harness.update(&mut tree, 0.6);
+        harness.update(&mut tree, 0.6);
+        assert_eq!(harness.animator.animation, AnimationType::Idle);
+        assert_eq!(harness.update(&mut tree, 0.6).0, NodeStatus::Success);
+        assert_eq!(harness.animator.animation, AnimationType::Run);
+    }
+
+    #[test]
+    fn test_heal_ally_picks_most_injured_in_range() {
+        let mut tree = BehaviourTree::new(vec![BehaviourNode::Action(BehaviourAction::HealAlly { amount: 10.0, range: 50.0 })]);
+        let mut harness = Harness::new();
+        let (near, injured, far) = (Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3));
+        for (ally, position, percent) in [(near, Vec2::new(10.0, 0.0), 0.8), (injured, Vec2::new(0.0, 40.0), 0.5), (far, Vec2::new(100.0, 0.0), 0.1)] {
+            harness.enemies.register(ally, EnemyType::Orc, position);
+            harness.health_percents.insert(ally, percent);
+        }
+        let (status, commands) = harness.update(&mut tree, 0.1);
+        assert_eq!(status, NodeStatus::Success);
+        assert_eq!(commands, vec![TreeCommand::Heal { target: injured, amount: 10.0 }]);
+    }
+}
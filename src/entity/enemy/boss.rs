@@ -0,0 +1,345 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::abilities::abilities::{aim_rotation, use_ability, AbilityBundle, AbilityCastEvent, AbilitySystem, AbilityType, EffectOwner};
+use crate::abilities::ability_particles::AbilityParticles;
+use crate::entity::{damage::AttackerSnapshot, health::{death_update, health_update, EntityType, Health, HealthDeathEvent}, stats::Stats};
+use crate::player::Player;
+
+use super::archer::spawn_arrow;
+use super::orc::get_player_pos;
+use super::spawner::EnemySpawning;
+use super::state_machine::StateMachine;
+use super::telegraph::{spawn_area_strike, PendingStrike};
+use super::EnemyType;
+
+/// Bosses can't be hurt while switching phase so the change always gets seen
+const PHASE_INVULNERABILITY: f32 = 1.5;
+const STRIKE_COLOUR: Color = Color::rgb(0.9, 0.1, 0.1);
+const STRIKE_KNOCKBACK: f32 = 150.0;
+/// Strikes after the first land within this distance of the player
+const STRIKE_SCATTER: f32 = 96.0;
+const ARENA_WALL_THICKNESS: f32 = 8.0;
+const ARENA_WALL_COLOUR: Color = Color::rgba(0.5, 0.1, 0.1, 0.8);
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Boss>()
+           .register_type::<Pattern>()
+           .register_type::<BossArena>()
+           .add_event::<BossEncounterStartedEvent>()
+           .add_event::<BossPhaseChangedEvent>()
+           .add_event::<BossDefeatedEvent>()
+           .add_systems(Startup, spawn_boss_arena)
+           .add_systems(Update, (
+               update_boss_arenas,
+               update_boss_phases.after(health_update),
+               pattern_enter,
+               run_boss_patterns.after(pattern_enter).after(update_boss_phases),
+               detect_boss_defeat.after(health_update).before(death_update),
+               unlock_boss_arenas.after(detect_boss_defeat)
+           ));
+    }
+}
+
+/// A single attack a boss performs, phases run theirs in order and loop
+#[derive(Debug, Clone, Reflect, Deserialize)]
+pub enum AttackPattern {
+    /// `count` projectiles fired evenly around the boss
+    ProjectileRing { count: u32, speed: f32, damage: f32 },
+    /// `count` enemies spawned `radius` away from the boss
+    Summon { enemy_type: EnemyType, count: u32, radius: f32 },
+    /// Telegraphed squares around the player that hit after `delay`, the first always lands on them
+    AreaStrikes { count: u32, size: f32, delay: f32, damage: f32 },
+    /// Casts from the boss' `AbilitySystem`, skipped if it is on cooldown
+    Ability(AbilityType),
+}
+
+#[derive(Debug, Clone, Reflect, Deserialize)]
+pub struct BossPhase {
+    /// Health percentage in `[0, 1]` the phase starts at, phases are listed from highest to lowest
+    pub threshold: f32,
+    /// Seconds the boss stands still after each pattern
+    pub recovery: f32,
+    pub patterns: Vec<AttackPattern>,
+    /// Run once as the phase starts
+    #[serde(default)]
+    pub on_enter: Vec<AttackPattern>,
+}
+
+/// The `boss` section of an enemy definition
+#[derive(Debug, Clone, Deserialize)]
+pub struct BossDefinition {
+    pub title: String,
+    pub phases: Vec<BossPhase>,
+}
+
+impl BossDefinition {
+    /// Every ability used by any phase, so the boss' `AbilitySystem` has a slot for each
+    pub fn abilities(&self) -> Vec<AbilityType> {
+        let mut abilities: Vec<AbilityType> = Vec::new();
+        let patterns = self.phases.iter().flat_map(|phase| phase.patterns.iter().chain(phase.on_enter.iter()));
+        for pattern in patterns {
+            if let AttackPattern::Ability(ability_type) = pattern {
+                if !abilities.contains(ability_type) {
+                    abilities.push(*ability_type);
+                }
+            }
+        }
+        abilities
+    }
+}
+
+#[derive(Component, Reflect)]
+pub struct Boss {
+    pub title: String,
+    pub phases: Vec<BossPhase>,
+    pub phase: usize,
+    pub next_pattern: usize,
+    /// Patterns to run this frame, filled by phase changes and the `Pattern` state
+    queued: Vec<AttackPattern>
+}
+
+impl Boss {
+    pub fn new(definition: &BossDefinition) -> Self {
+        Boss { title: definition.title.clone(), phases: definition.phases.clone(), phase: 0, next_pattern: 0, queued: Vec::new() }
+    }
+
+    pub fn current_phase(&self) -> Option<&BossPhase> {
+        self.phases.get(self.phase)
+    }
+}
+
+/// Enemy state for bosses, runs one pattern from the current phase then recovers
+#[derive(Component, Reflect, Debug, Clone)]
+pub struct Pattern;
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArenaState {
+    /// Waiting for the player to walk in
+    Waiting,
+    Active(Entity),
+    Cleared
+}
+
+/// Spawns its boss and walls the player in once they get within `trigger_radius`, the walls drop when the boss dies
+#[derive(Component, Reflect)]
+pub struct BossArena {
+    pub enemy_type: EnemyType,
+    pub half_size: Vec2,
+    pub trigger_radius: f32,
+    pub state: ArenaState,
+    walls: Vec<Entity>,
+    started_at: f32
+}
+
+impl BossArena {
+    pub fn new(enemy_type: EnemyType, half_size: Vec2, trigger_radius: f32) -> Self {
+        BossArena { enemy_type, half_size, trigger_radius, state: ArenaState::Waiting, walls: Vec::new(), started_at: 0.0 }
+    }
+}
+
+#[derive(Event)]
+pub struct BossEncounterStartedEvent {
+    pub boss: Entity,
+    pub arena: Entity
+}
+
+#[derive(Event)]
+pub struct BossPhaseChangedEvent {
+    pub boss: Entity,
+    pub phase: usize
+}
+
+/// Sent when a boss dies, `duration` is how long the fight took
+#[derive(Event)]
+pub struct BossDefeatedEvent {
+    pub boss: Entity,
+    pub title: String,
+    pub killer: Option<Entity>,
+    pub duration: f32
+}
+
+fn spawn_boss_arena(mut commands: Commands) {
+    commands.spawn((
+        BossArena::new(EnemyType::Warlord, Vec2::splat(160.0), 96.0),
+        TransformBundle::from_transform(Transform::from_xyz(320.0, -320.0, 0.0)),
+        Name::new("Boss Arena")
+    ));
+}
+
+fn spawn_arena_walls(commands: &mut Commands, centre: Vec2, half_size: Vec2) -> Vec<Entity> {
+    let horizontal = Vec2::new(half_size.x + ARENA_WALL_THICKNESS, ARENA_WALL_THICKNESS);
+    let vertical = Vec2::new(ARENA_WALL_THICKNESS, half_size.y + ARENA_WALL_THICKNESS);
+    [
+        (Vec2::new(0.0, half_size.y), horizontal),
+        (Vec2::new(0.0, -half_size.y), horizontal),
+        (Vec2::new(half_size.x, 0.0), vertical),
+        (Vec2::new(-half_size.x, 0.0), vertical),
+    ].into_iter().map(|(offset, wall_half_size)| {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite { color: ARENA_WALL_COLOUR, custom_size: Some(wall_half_size * 2.0), ..default() },
+                transform: Transform::from_translation((centre + offset).extend(-2.0)),
+                ..default()
+            },
+            Collider::cuboid(wall_half_size.x, wall_half_size.y),
+            Name::new("Arena Wall")
+        )).id()
+    }).collect()
+}
+
+fn update_boss_arenas(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut spawning: EnemySpawning,
+    player_query: Query<&Transform, With<Player>>,
+    mut arenas: Query<(Entity, &mut BossArena, &Transform)>,
+    mut ev_started: EventWriter<BossEncounterStartedEvent>
+) {
+    let Some(player_pos) = get_player_pos(player_query.get_single()) else { return; };
+    for (entity, mut arena, transform) in arenas.iter_mut() {
+        if arena.state != ArenaState::Waiting { continue; }
+        let centre = transform.translation.truncate();
+        if player_pos.distance_squared(centre) > arena.trigger_radius.powi(2) { continue; }
+        let Some(boss) = spawning.spawn(&mut commands, arena.enemy_type, centre) else { continue; };
+        arena.walls = spawn_arena_walls(&mut commands, centre, arena.half_size);
+        arena.state = ArenaState::Active(boss);
+        arena.started_at = time.elapsed_seconds();
+        ev_started.send(BossEncounterStartedEvent { boss, arena: entity });
+    }
+}
+
+fn update_boss_phases(
+    mut bosses: Query<(Entity, &mut Boss, &mut Health)>,
+    mut ev_phase: EventWriter<BossPhaseChangedEvent>
+) {
+    for (entity, mut boss, mut health) in bosses.iter_mut() {
+        if health.is_dead() { continue; }
+        let percent = health.get_percent();
+        // NOTE: Takes the lowest phase crossed so a big hit can skip straight past a phase
+        let Some(phase) = boss.phases.iter().rposition(|phase| percent <= phase.threshold) else { continue; };
+        if phase <= boss.phase { continue; }
+        boss.phase = phase;
+        boss.next_pattern = 0;
+        let on_enter = boss.phases[phase].on_enter.clone();
+        boss.queued.extend(on_enter);
+        health.make_invulnerable(PHASE_INVULNERABILITY);
+        info!("{} entered phase {}", boss.title, phase + 1);
+        ev_phase.send(BossPhaseChangedEvent { boss: entity, phase });
+    }
+}
+
+/// Queues the phase's next pattern, standing still for the phase's recovery is left to the definition's hooks
+fn pattern_enter(
+    mut bosses: Query<(&mut StateMachine, &mut Boss), Added<Pattern>>
+) {
+    for (mut machine, mut boss) in bosses.iter_mut() {
+        let Some(phase) = boss.current_phase() else { continue; };
+        let recovery = phase.recovery;
+        let pattern = (!phase.patterns.is_empty()).then(|| phase.patterns[boss.next_pattern % phase.patterns.len()].clone());
+        machine.start_timer(recovery);
+        if let Some(pattern) = pattern {
+            boss.next_pattern += 1;
+            boss.queued.push(pattern);
+        }
+    }
+}
+
+fn run_boss_patterns(
+    mut commands: Commands,
+    mut spawning: EnemySpawning,
+    mut ability_sprites: ResMut<AbilityBundle>,
+    ability_particles: Res<AbilityParticles>,
+    mut ev_cast: EventWriter<AbilityCastEvent>,
+    player_query: Query<&Transform, With<Player>>,
+    mut bosses: Query<(Entity, &mut Boss, &Transform, &Stats, Option<&mut AbilitySystem>)>
+) {
+    let player_pos = get_player_pos(player_query.get_single());
+    let mut rng = rand::thread_rng();
+    for (entity, mut boss, transform, stats, mut ability_system) in bosses.iter_mut() {
+        if boss.queued.is_empty() { continue; }
+        let position = transform.translation.truncate();
+        let attacker = AttackerSnapshot::from_stats(stats);
+        for pattern in std::mem::take(&mut boss.queued) {
+            match pattern {
+                AttackPattern::ProjectileRing { count, speed, damage } => {
+                    for i in 0..count {
+                        let direction = Vec2::from_angle(std::f32::consts::TAU * i as f32 / count as f32);
+                        spawn_arrow(&mut commands, entity, position, direction, speed, damage, attacker);
+                    }
+                },
+                AttackPattern::Summon { enemy_type, count, radius } => {
+                    for i in 0..count {
+                        let direction = Vec2::from_angle(std::f32::consts::TAU * i as f32 / count as f32);
+                        spawning.spawn(&mut commands, enemy_type, position + direction * radius);
+                    }
+                },
+                AttackPattern::AreaStrikes { count, size, delay, damage } => {
+                    let Some(player_pos) = player_pos else { continue; };
+                    for i in 0..count {
+                        let target = if i == 0 {
+                            player_pos
+                        } else {
+                            player_pos + Vec2::new(rng.gen_range(-STRIKE_SCATTER..STRIKE_SCATTER), rng.gen_range(-STRIKE_SCATTER..STRIKE_SCATTER))
+                        };
+                        spawn_area_strike(&mut commands, target, size, delay, STRIKE_COLOUR, PendingStrike { damage, knockback: STRIKE_KNOCKBACK, attacker, owner: entity });
+                    }
+                },
+                AttackPattern::Ability(ability_type) => {
+                    let Some(ability_system) = ability_system.as_mut() else { continue; };
+                    let Some(ability) = ability_system.abilities.iter_mut().find(|ability| ability.ability_data.ability_type == ability_type && ability.can_use()) else { continue; };
+                    let direction = player_pos.map_or(Vec2::NEG_Y, |player_pos| (player_pos - position).normalize_or_zero());
+                    ev_cast.send(AbilityCastEvent { caster: entity, ability_type });
+                    use_ability(
+                        ability,
+                        EffectOwner(entity, EntityType::Boss),
+                        transform,
+                        aim_rotation(direction),
+                        attacker,
+                        &mut commands,
+                        &mut ability_sprites,
+                        &ability_particles
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn detect_boss_defeat(
+    time: Res<Time>,
+    mut evr_death: EventReader<HealthDeathEvent>,
+    bosses: Query<&Boss>,
+    arenas: Query<&BossArena>,
+    mut ev_defeated: EventWriter<BossDefeatedEvent>
+) {
+    for death_event in evr_death.read() {
+        let Ok(boss) = bosses.get(death_event.entity) else { continue; };
+        let duration = arenas.iter()
+            .find(|arena| arena.state == ArenaState::Active(death_event.entity))
+            .map_or(0.0, |arena| time.elapsed_seconds() - arena.started_at);
+        info!("{} defeated in {:.1}s", boss.title, duration);
+        ev_defeated.send(BossDefeatedEvent { boss: death_event.entity, title: boss.title.clone(), killer: death_event.killer, duration });
+    }
+}
+
+fn unlock_boss_arenas(
+    mut commands: Commands,
+    mut evr_defeated: EventReader<BossDefeatedEvent>,
+    mut arenas: Query<&mut BossArena>
+) {
+    for defeated_event in evr_defeated.read() {
+        for mut arena in arenas.iter_mut() {
+            if arena.state != ArenaState::Active(defeated_event.boss) { continue; }
+            for wall in arena.walls.drain(..) {
+                commands.entity(wall).despawn_recursive();
+            }
+            arena.state = ArenaState::Cleared;
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::abilities::abilities::{aim_rotation, use_ability, AbilityBundle, AbilityCastEvent, AbilitySystem, AbilityType, EffectOwner};
+use crate::abilities::ability_particles::AbilityParticles;
+use crate::animation::directional_animator::{AnimationType, DirectionalAnimator};
+use crate::entity::{damage::AttackerSnapshot, health::EntityType, stats::Stats};
+use crate::pathfinding::AITarget;
+use crate::player::Player;
+
+use super::orc::get_player_pos;
+use super::state_machine::{StateMachine, Trigger};
+use super::telegraph::{spawn_area_telegraph, spawn_line_telegraph};
+use super::{Enemy, EnemyBehaviour};
+
+const TELEGRAPH_COLOUR: Color = Color::rgb(0.6, 0.2, 0.9);
+const LINE_TELEGRAPH_WIDTH: f32 = 32.0;
+/// Roughly how far a fire ball travels before it burns out
+const LINE_TELEGRAPH_LENGTH: f32 = 200.0;
+const AREA_TELEGRAPH_SIZE: f32 = 128.0;
+
+pub struct CasterPlugin;
+
+impl Plugin for CasterPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Cast>()
+           .add_systems(Update, cast_update);
+    }
+}
+
+/// `slot` is set once an ability has been picked and its telegraph is showing
+#[derive(Component, Reflect, Debug, Clone, Default)]
+pub struct Cast {
+    pub slot: Option<usize>,
+    pub target: Vec2
+}
+
+fn cast_update(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut ability_sprites: ResMut<AbilityBundle>,
+    ability_particles: Res<AbilityParticles>,
+    mut ev_cast: EventWriter<AbilityCastEvent>,
+    player_query: Query<&Transform, With<Player>>,
+    mut casters: Query<(Entity, &mut Enemy, &mut StateMachine, &mut Cast, &EnemyBehaviour, &mut AbilitySystem, &mut DirectionalAnimator, &AITarget, &Transform, &Stats)>
+) {
+    let player_pos = get_player_pos(player_query.get_single());
+    for (entity, mut enemy, mut machine, mut cast, behaviour, mut ability_system, mut anim, ai, transform, stats) in casters.iter_mut() {
+        let EnemyBehaviour::Caster { telegraph, .. } = behaviour else { continue; };
+        let position = transform.translation.truncate();
+        let Some(slot) = cast.slot else {
+            let in_range = player_pos.is_some_and(|player_pos| position.distance(player_pos) <= ai.attack_range);
+            let ready = ability_system.abilities.iter().position(|ability| ability.can_use());
+            match (in_range, ready, player_pos) {
+                (true, Some(slot), Some(player_pos)) => {
+                    let direction = (player_pos - position).normalize_or_zero();
+                    let ability_type = ability_system.abilities[slot].ability_data.ability_type;
+                    match ability_type {
+                        AbilityType::IceStorm => { spawn_area_telegraph(&mut commands, player_pos, AREA_TELEGRAPH_SIZE, *telegraph, TELEGRAPH_COLOUR); },
+                        _ => { spawn_line_telegraph(&mut commands, position, direction, LINE_TELEGRAPH_LENGTH, LINE_TELEGRAPH_WIDTH, *telegraph, TELEGRAPH_COLOUR); },
+                    }
+                    cast.slot = Some(slot);
+                    cast.target = player_pos;
+                    enemy.action_timer = Timer::from_seconds(*telegraph, TimerMode::Once);
+                    anim.update_animation(AnimationType::Attack);
+                },
+                // NOTE: Nothing off cooldown yet, keep standing still unless the player has moved out of range
+                (true, None, _) => {},
+                _ => machine.signal(Trigger::Finished)
+            }
+            continue;
+        };
+        enemy.action_timer.tick(Duration::from_secs_f32(time.delta_seconds()));
+        if !enemy.action_timer.finished() { continue; }
+        // NOTE: Aims at where the telegraph was shown so the player can dodge by moving out of it
+        let direction = (cast.target - position).normalize_or_zero();
+        if let Some(ability) = ability_system.get_ability(slot) {
+            ev_cast.send(AbilityCastEvent { caster: entity, ability_type: ability.ability_data.ability_type });
+            use_ability(
+                ability,
+                EffectOwner(entity, EntityType::Enemy),
+                transform,
+                aim_rotation(direction),
+                AttackerSnapshot::from_stats(stats),
+                &mut commands,
+                &mut ability_sprites,
+                &ability_particles
+            );
+        }
+        machine.signal(Trigger::Finished);
+    }
+}
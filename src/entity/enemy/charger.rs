@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::entity::{damage::{AttackerSnapshot, DamageType}, health::{DamageInstance, Health}, hit_reaction::Stunned, stats::Stats};
+use crate::player::Player;
+
+use super::orc::get_player_pos;
+use super::state_machine::{StateMachine, Trigger};
+use super::telegraph::spawn_line_telegraph;
+use super::EnemyBehaviour;
+
+const TELEGRAPH_COLOUR: Color = Color::rgb(0.9, 0.3, 0.1);
+const TELEGRAPH_WIDTH: f32 = 40.0;
+/// How far ahead of the charger to check for walls each frame
+const WALL_CHECK_DISTANCE: f32 = 20.0;
+
+pub struct ChargerPlugin;
+
+impl Plugin for ChargerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WindUp>()
+           .register_type::<Dash>()
+           .register_type::<Stagger>()
+           .add_systems(Update, (
+               windup_enter,
+               dash_enter.before(dash_update),
+               dash_update,
+               stagger_enter
+           ));
+    }
+}
+
+#[derive(Component, Reflect, Debug, Clone)]
+pub struct WindUp;
+
+/// `direction` is locked in when the wind up finishes, `hit` stops the player being damaged twice per dash
+#[derive(Component, Reflect, Debug, Clone, Default)]
+pub struct Dash {
+    pub direction: Vec2,
+    pub hit: bool
+}
+
+#[derive(Component, Reflect, Debug, Clone)]
+pub struct Stagger;
+
+fn windup_enter(
+    mut commands: Commands,
+    player_query: Query<&Transform, With<Player>>,
+    mut chargers: Query<(&mut StateMachine, &EnemyBehaviour, &Transform), Added<WindUp>>
+) {
+    let player_pos = get_player_pos(player_query.get_single());
+    for (mut machine, behaviour, transform) in chargers.iter_mut() {
+        let EnemyBehaviour::Charger { windup, dash_speed, dash_duration, .. } = behaviour else { continue; };
+        machine.start_timer(*windup);
+        let Some(player_pos) = player_pos else { continue; };
+        let position = transform.translation.truncate();
+        spawn_line_telegraph(&mut commands, position, player_pos - position, dash_speed * dash_duration, TELEGRAPH_WIDTH, *windup, TELEGRAPH_COLOUR);
+    }
+}
+
+fn dash_enter(
+    player_query: Query<&Transform, With<Player>>,
+    mut chargers: Query<(&mut StateMachine, &mut Dash, &EnemyBehaviour, &Transform), Added<Dash>>
+) {
+    let player_pos = get_player_pos(player_query.get_single());
+    for (mut machine, mut dash, behaviour, transform) in chargers.iter_mut() {
+        let EnemyBehaviour::Charger { dash_duration, .. } = behaviour else { continue; };
+        machine.start_timer(*dash_duration);
+        // NOTE: Aims at the player as the dash starts, which lines up with the telegraph unless they moved
+        dash.direction = player_pos.map_or(Vec2::ZERO, |player_pos| (player_pos - transform.translation.truncate()).normalize_or_zero());
+        dash.hit = false;
+    }
+}
+
+fn dash_update(
+    mut commands: Commands,
+    rapier: Res<RapierContext>,
+    mut player_query: Query<(Entity, &mut Health), With<Player>>,
+    mut chargers: Query<(Entity, &mut StateMachine, &mut Dash, &EnemyBehaviour, &Stats, &Transform, &mut Velocity), Without<Player>>,
+    solid: Query<(), Without<Health>>
+) {
+    let mut player = player_query.get_single_mut().ok();
+    for (entity, mut machine, mut dash, behaviour, stats, transform, mut velocity) in chargers.iter_mut() {
+        let EnemyBehaviour::Charger { dash_speed, damage, knockback, wall_stun, .. } = behaviour else { continue; };
+        if let Some((player_entity, player_health)) = player.as_mut() {
+            if !dash.hit && rapier.intersection_pair(*player_entity, entity).is_some() {
+                player_health.push(
+                    DamageInstance::new(*damage, DamageType::PHYSICAL, true)
+                        .with_attacker(AttackerSnapshot::from_stats(stats))
+                        .with_source(entity)
+                        .with_knockback(dash.direction * *knockback)
+                );
+                dash.hit = true;
+            }
+        }
+        // NOTE: Chargers are sensors and pass through walls, so walls are found by casting ahead for anything solid without health
+        let filter = QueryFilter::default().exclude_sensors().exclude_collider(entity);
+        let blocked = rapier.cast_ray(transform.translation.truncate(), dash.direction, WALL_CHECK_DISTANCE, true, filter)
+            .is_some_and(|(hit, _)| solid.contains(hit));
+        if blocked {
+            velocity.linvel = Vec2::ZERO;
+            commands.entity(entity).insert(Stunned { remaining: *wall_stun });
+            machine.signal(Trigger::Blocked);
+            continue;
+        }
+        velocity.linvel = dash.direction * *dash_speed;
+    }
+}
+
+fn stagger_enter(
+    mut chargers: Query<(&mut StateMachine, &EnemyBehaviour), Added<Stagger>>
+) {
+    for (mut machine, behaviour) in chargers.iter_mut() {
+        if let EnemyBehaviour::Charger { wall_stun, .. } = behaviour {
+            machine.start_timer(*wall_stun);
+        }
+    }
+}
@@ -1,73 +1,326 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::{BoxedFuture, HashMap},
+};
+use bevy_rapier2d::prelude::Collider;
+use rand::Rng;
+use serde::Deserialize;
+use thiserror::Error;
+
 use crate::{
     animation::{directional_animator::*, *},
-    entity::health::Health,
-    entity::stats::Stats,
+    entity::{health::HealthDeathEvent, stats::{Stats, StatType}},
 };
-use bevy::{math::Vec2, utils::hashbrown::HashMap};
+use super::behaviour_tree::BehaviourNode;
+use super::boss::BossDefinition;
+use super::elite::{self, Elite};
+use super::perception::PerceptionDefinition;
+use super::state_machine::{StateConfig, StateMachine, Transition};
+use super::territory::TerritoryDefinition;
+use super::{EnemyBehaviour, EnemyState, EnemyType};
 
-#[derive(Clone)]
-pub struct EnemyData {
-    pub animator: DirectionalAnimator,
-    pub sprite_data: SpriteData,
-    pub health: Health,
-    pub stats: Stats,
+/// Everything needed to spawn an enemy archetype, loaded from `assets/enemy/*.enemy.ron`
+#[derive(Asset, TypePath, Deserialize, Clone)]
+pub struct EnemyDefinition {
+    pub enemy_type: EnemyType,
+    pub name: String,
+    pub sprite: SpriteData,
+    /// Multiplied over the sprite so archetypes sharing a sheet can be told apart
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32)>,
+    pub animations: HashMap<AnimationType, HashMap<AnimationDirection, AnimationClip>>,
+    pub stats: StatsDefinition,
+    pub collider: ColliderDefinition,
+    pub ai: AIDefinition,
+    #[serde(default)]
+    pub perception: PerceptionDefinition,
+    /// Keeps the enemy near where it spawned, enemies without one roam freely
+    #[serde(default)]
+    pub territory: Option<TerritoryDefinition>,
+    pub behaviour: EnemyBehaviour,
+    #[serde(default)]
+    pub initial_state: EnemyState,
+    /// Enter and exit hooks, states without an entry just have none
+    #[serde(default)]
+    pub states: Vec<StateConfig>,
+    /// Checked in order every frame, see `StateMachine`
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    /// Drives the enemy with a `BehaviourTree` instead of the state machine when set
+    #[serde(default)]
+    pub behaviour_tree: Option<Vec<BehaviourNode>>,
+    #[serde(default)]
+    pub invulnerability: f32,
+    #[serde(default)]
+    pub hit_reaction: HitReactionDefinition,
+    #[serde(default)]
+    pub health_bar_offset: (f32, f32),
+    pub xp: f32,
+    #[serde(default)]
+    pub loot: Vec<LootEntry>,
+    /// Makes this archetype a boss with phases and attack patterns
+    #[serde(default)]
+    pub boss: Option<BossDefinition>,
 }
 
-#[derive(Clone)]
+#[derive(Deserialize, Clone)]
 pub struct SpriteData {
-    pub path: &'static str,
-    pub tile_size: Vec2,
+    pub path: String,
+    pub tile_size: (f32, f32),
     pub columns: usize,
     pub rows: usize,
-    pub padding: Option<Vec2>,
-    pub offset: Option<Vec2>,
-}
-
-pub fn orc_data() -> EnemyData {
-    return EnemyData {
-        animator: DirectionalAnimator {
-            animation_indices: HashMap::from([
-                (
-                    AnimationType::Idle,
-                    HashMap::from([
-                        (AnimationDirection::Up, AnimationIndices::new(0, 6)),
-                        (AnimationDirection::Left, AnimationIndices::new(9, 15)),
-                        (AnimationDirection::Down, AnimationIndices::new(18, 24)),
-                        (AnimationDirection::Right, AnimationIndices::new(27, 33)),
-                    ]),
-                ),
-                (
-                    AnimationType::Walk,
-                    HashMap::from([
-                        (AnimationDirection::Up, AnimationIndices::new(36, 44)),
-                        (AnimationDirection::Left, AnimationIndices::new(45, 53)),
-                        (AnimationDirection::Down, AnimationIndices::new(54, 62)),
-                        (AnimationDirection::Right, AnimationIndices::new(63, 71)),
-                    ]),
-                ),
-                (
-                    AnimationType::Attack,
-                    HashMap::from([
-                        (AnimationDirection::Up, AnimationIndices::new(72, 77)),
-                        (AnimationDirection::Left, AnimationIndices::new(81, 86)),
-                        (AnimationDirection::Down, AnimationIndices::new(90, 95)),
-                        (AnimationDirection::Right, AnimationIndices::new(99, 104)),
-                    ]),
-                ),
-            ]),
+    #[serde(default)]
+    pub padding: Option<(f32, f32)>,
+    #[serde(default)]
+    pub offset: Option<(f32, f32)>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct AnimationClip {
+    pub first: usize,
+    pub last: usize,
+    /// Defaults to playing the whole clip over one second, see `AnimationIndices::new`
+    #[serde(default)]
+    pub frame_length: Option<f32>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct StatsDefinition {
+    pub health: f32,
+    pub defence: f32,
+    pub magic_defence: f32,
+    pub speed: f32,
+    pub attack: f32,
+    pub magic: f32,
+    /// Added on top of the base stats, for anything `Stats::new` doesn't take
+    #[serde(default)]
+    pub bonus: HashMap<StatType, f32>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum ColliderDefinition {
+    Ball(f32),
+    Cuboid(f32, f32),
+    Capsule(f32, f32),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct AIDefinition {
+    pub follow_range: f32,
+    pub attack_range: f32,
+    #[serde(default)]
+    pub damping: f32,
+    /// Shares attack slots with other coordinated enemies instead of all piling onto the player
+    #[serde(default)]
+    pub coordinated: bool,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct HitReactionDefinition {
+    pub stun_duration: f32,
+    pub knockback_multiplier: f32,
+}
+
+impl Default for HitReactionDefinition {
+    fn default() -> Self {
+        HitReactionDefinition { stun_duration: 0.0, knockback_multiplier: 1.0 }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LootEntry {
+    pub item: String,
+    /// Chance in `[0, 1]` for this entry to drop
+    pub chance: f32,
+    #[serde(default = "default_loot_amount")]
+    pub amount: (u32, u32),
+}
+
+fn default_loot_amount() -> (u32, u32) {
+    (1, 1)
+}
+
+impl EnemyDefinition {
+    pub fn animator(&self) -> DirectionalAnimator {
+        let animation_indices = self.animations.iter()
+            .map(|(animation, clips)| (*animation, clips.iter().map(|(direction, clip)| (*direction, clip.indices())).collect()))
+            .collect();
+        DirectionalAnimator {
+            animation_indices,
             animation: AnimationType::Idle,
-            direction: AnimationDirection::Up,
+            direction: AnimationDirection::Down,
             last_update_timer: 0.0
-        },
-        sprite_data: SpriteData {
-            path: "enemy/orc.png",
-            tile_size: Vec2::splat(64.0),
-            columns: 9,
-            rows: 12,
-            padding: None,
-            offset: None,
-        },
-        stats: Stats::new(1000.0, 25.0, 5.0, 25.0, 10.0, 0.0),
-        health: Health::new(1000.0, 25, 5, crate::entity::health::EntityType::Enemy),
-    };
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        let definition = &self.stats;
+        let mut stats = Stats::new(definition.health, definition.defence, definition.magic_defence, definition.speed, definition.attack, definition.magic);
+        for (stat_type, amount) in definition.bonus.iter() {
+            stats.add_stat(*stat_type, *amount);
+        }
+        stats
+    }
+
+    pub fn collider(&self) -> Collider {
+        match self.collider {
+            ColliderDefinition::Ball(radius) => Collider::ball(radius),
+            ColliderDefinition::Cuboid(half_width, half_height) => Collider::cuboid(half_width, half_height),
+            ColliderDefinition::Capsule(half_height, radius) => Collider::capsule_y(half_height, radius),
+        }
+    }
+
+    pub fn state_machine(&self) -> StateMachine {
+        StateMachine::new(self.initial_state.clone(), self.states.clone(), self.transitions.clone())
+    }
+
+    pub fn colour(&self) -> Color {
+        self.tint.map_or(Color::WHITE, |(r, g, b)| Color::rgb(r, g, b))
+    }
+
+    pub fn atlas_layout(&self) -> TextureAtlasLayout {
+        let sprite = &self.sprite;
+        TextureAtlasLayout::from_grid(
+            Vec2::from(sprite.tile_size),
+            sprite.columns,
+            sprite.rows,
+            sprite.padding.map(Vec2::from),
+            sprite.offset.map(Vec2::from)
+        )
+    }
+}
+
+impl AnimationClip {
+    fn indices(&self) -> AnimationIndices {
+        match self.frame_length {
+            Some(frame_length) => AnimationIndices::with_frame_length(self.first, self.last, frame_length),
+            None => AnimationIndices::new(self.first, self.last),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EnemyDefinitionLoader;
+
+#[derive(Debug, Error)]
+pub enum EnemyDefinitionLoaderError {
+    #[error("Could not read enemy definition: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse enemy definition: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for EnemyDefinitionLoader {
+    type Asset = EnemyDefinition;
+    type Settings = ();
+    type Error = EnemyDefinitionLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes::<EnemyDefinition>(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemy.ron"]
+    }
+}
+
+/// Handles for every enemy archetype, plus the atlas layout built from each one once it loads
+#[derive(Resource, Default)]
+pub struct EnemyDefinitions {
+    pub definitions: HashMap<EnemyType, Handle<EnemyDefinition>>,
+    layouts: HashMap<EnemyType, Handle<TextureAtlasLayout>>,
+}
+
+impl EnemyDefinitions {
+    pub fn get<'a>(&self, enemy_type: EnemyType, assets: &'a Assets<EnemyDefinition>) -> Option<&'a EnemyDefinition> {
+        self.definitions.get(&enemy_type).and_then(|handle| assets.get(handle))
+    }
+
+    pub fn layout(&mut self, definition: &EnemyDefinition, atlases: &mut Assets<TextureAtlasLayout>) -> Handle<TextureAtlasLayout> {
+        self.layouts.entry(definition.enemy_type)
+            .or_insert_with(|| atlases.add(definition.atlas_layout()))
+            .clone()
+    }
+}
+
+pub fn enemy_definition_path(enemy_type: EnemyType) -> &'static str {
+    match enemy_type {
+        EnemyType::Orc => "enemy/orc.enemy.ron",
+        EnemyType::Archer => "enemy/archer.enemy.ron",
+        EnemyType::Caster => "enemy/caster.enemy.ron",
+        EnemyType::Charger => "enemy/charger.enemy.ron",
+        EnemyType::Warlord => "enemy/warlord.enemy.ron",
+        EnemyType::Shaman => "enemy/shaman.enemy.ron",
+    }
+}
+
+pub fn load_enemy_definitions(mut definitions: ResMut<EnemyDefinitions>, assets: Res<AssetServer>) {
+    for enemy_type in EnemyType::ALL {
+        definitions.definitions.insert(enemy_type, assets.load(enemy_definition_path(enemy_type)));
+    }
+}
+
+/// Sent when an enemy with a loot table dies, an item system can turn these into pickups
+#[derive(Event)]
+pub struct LootDropEvent {
+    pub item: String,
+    pub amount: u32,
+    pub position: Vec2,
+}
+
+/// Kept on the enemy so its loot can be rolled on death without looking the definition up again
+#[derive(Component, Clone)]
+pub struct LootTable(pub Vec<LootEntry>);
+
+impl LootTable {
+    /// Rolls every entry, returning `(item, amount)` for each one that dropped
+    pub fn roll(&self, rng: &mut impl Rng) -> Vec<(String, u32)> {
+        self.0.iter()
+            .filter(|entry| rng.gen::<f32>() < entry.chance)
+            .map(|entry| (entry.item.clone(), rng.gen_range(entry.amount.0..=entry.amount.1.max(entry.amount.0))))
+            .collect()
+    }
+}
+
+pub fn drop_loot(
+    mut evr_death: EventReader<HealthDeathEvent>,
+    mut ev_loot: EventWriter<LootDropEvent>,
+    enemies: Query<(&LootTable, &Transform, Has<Elite>)>
+) {
+    let mut rng = rand::thread_rng();
+    for death_event in evr_death.read() {
+        let Ok((loot_table, transform, elite)) = enemies.get(death_event.entity) else { continue; };
+        let rolls = if elite { elite::LOOT_ROLLS } else { 1 };
+        for (item, amount) in (0..rolls).flat_map(|_| loot_table.roll(&mut rng)) {
+            info!("{:?} dropped {} x{}", death_event.entity, item, amount);
+            ev_loot.send(LootDropEvent { item, amount, position: transform.translation.truncate() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_enemy_definitions_parse() {
+        for enemy_type in EnemyType::ALL {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(enemy_definition_path(enemy_type));
+            let contents = std::fs::read_to_string(&path).unwrap_or_else(|error| panic!("Could not read {}: {}", path.display(), error));
+            let definition = ron::de::from_str::<EnemyDefinition>(&contents).unwrap_or_else(|error| panic!("Could not parse {}: {}", path.display(), error));
+            assert_eq!(definition.enemy_type, enemy_type, "{} defines the wrong enemy type", path.display());
+        }
+    }
 }
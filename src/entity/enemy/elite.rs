@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::entity::{combat_log::{StatusAppliedEvent, StatusKind}, experience::ExperienceReward, hit_reaction::HitReaction};
+use crate::entity::health::{Health, HealthDamageEvent, Shield};
+use crate::entity::stats::{Stats, StatModifier, StatType};
+use crate::player::Player;
+
+use super::waves::scale_wave_enemies;
+
+pub(super) const HEALTH_BONUS: f32 = 0.5;
+pub(super) const ATTACK_BONUS: f32 = 0.25;
+const XP_MULTIPLIER: f32 = 2.0;
+const SCALE: f32 = 1.2;
+/// Times an elite's loot table is rolled when it dies
+pub const LOOT_ROLLS: usize = 2;
+const MAX_AFFIXES: usize = 2;
+const HASTE_BONUS: f32 = 0.4;
+const VAMPIRIC_FRACTION: f32 = 0.5;
+/// Fraction of max health given as a shield
+const SHIELD_FRACTION: f32 = 0.3;
+const AURA_RADIUS: f32 = 80.0;
+const AURA_INTERVAL: f32 = 0.5;
+const AURA_SLOW: f32 = 0.3;
+/// NOTE: Slightly longer than the interval so the slow doesn't flicker off between pulses
+const AURA_SLOW_DURATION: f32 = 0.75;
+const AURA_Z: f32 = -0.5;
+
+pub struct ElitePlugin;
+
+impl Plugin for ElitePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Elite>()
+           .register_type::<EliteAffix>()
+           .register_type::<Vampiric>()
+           .register_type::<FrozenAura>()
+           .add_systems(Update, (apply_elite_bonuses.after(scale_wave_enemies), vampiric_heals, frozen_aura_update));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum EliteAffix {
+    Hasted,
+    Vampiric,
+    FrozenAura,
+    Shielded,
+}
+
+impl EliteAffix {
+    pub const ALL: [EliteAffix; 4] = [EliteAffix::Hasted, EliteAffix::Vampiric, EliteAffix::FrozenAura, EliteAffix::Shielded];
+
+    /// Prefixed onto the enemy's name
+    pub fn label(&self) -> &'static str {
+        match self {
+            EliteAffix::Hasted => "Hasted",
+            EliteAffix::Vampiric => "Vampiric",
+            EliteAffix::FrozenAura => "Frozen",
+            EliteAffix::Shielded => "Shielded",
+        }
+    }
+
+    /// Multiplied into the sprite colour
+    pub fn colour(&self) -> Color {
+        match self {
+            EliteAffix::Hasted => Color::rgb(1.0, 1.0, 0.6),
+            EliteAffix::Vampiric => Color::rgb(1.0, 0.5, 0.5),
+            EliteAffix::FrozenAura => Color::rgb(0.6, 0.85, 1.0),
+            EliteAffix::Shielded => Color::rgb(0.85, 0.85, 0.85),
+        }
+    }
+}
+
+/// Rolled by spawners from their `elite_chance`, a tougher and larger version of the archetype with extra affixes
+#[derive(Component, Reflect)]
+pub struct Elite {
+    pub affixes: Vec<EliteAffix>
+}
+
+impl Elite {
+    /// Between one and `MAX_AFFIXES` different affixes
+    pub fn roll(rng: &mut impl Rng) -> Self {
+        let count = rng.gen_range(1..=MAX_AFFIXES);
+        Elite { affixes: EliteAffix::ALL.choose_multiple(rng, count).copied().collect() }
+    }
+
+    pub fn has(&self, affix: EliteAffix) -> bool {
+        self.affixes.contains(&affix)
+    }
+
+    pub fn prefix(&self) -> String {
+        self.affixes.iter().map(|affix| affix.label()).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn tint(&self, colour: Color) -> Color {
+        self.affixes.iter().fold(colour, |colour, affix| {
+            let tint = affix.colour();
+            Color::rgba(colour.r() * tint.r(), colour.g() * tint.g(), colour.b() * tint.b(), colour.a())
+        })
+    }
+}
+
+/// Heals the owner by `fraction` of the damage it deals
+#[derive(Component, Reflect)]
+pub struct Vampiric {
+    pub fraction: f32
+}
+
+/// Slows the player by `slow` while they are within `radius`, checked every `interval` seconds
+#[derive(Component, Reflect)]
+pub struct FrozenAura {
+    pub radius: f32,
+    pub slow: f32,
+    pub interval: f32,
+    pub timer: f32
+}
+
+fn apply_elite_bonuses(
+    mut commands: Commands,
+    mut elites: Query<(Entity, &Elite, &mut Stats, &mut Health, &mut Transform, &mut ExperienceReward, &mut Sprite, &mut HitReaction, &mut Name), Added<Elite>>,
+    mut ev_status: EventWriter<StatusAppliedEvent>
+) {
+    for (entity, elite, mut stats, mut health, mut transform, mut reward, mut sprite, mut hit_reaction, mut name) in elites.iter_mut() {
+        // NOTE: The health and attack bonuses are applied with the wave bonus in `scale_wave_enemies`
+        transform.scale *= SCALE;
+        reward.amount *= XP_MULTIPLIER;
+        sprite.color = elite.tint(sprite.color);
+        hit_reaction.base_colour = elite.tint(hit_reaction.base_colour);
+        name.set(format!("{} {}", elite.prefix(), name.as_str()));
+        for affix in elite.affixes.iter() {
+            match affix {
+                EliteAffix::Hasted => stats.add_modifier(StatType::Speed, StatModifier::percent(HASTE_BONUS, entity)),
+                EliteAffix::Vampiric => { commands.entity(entity).insert(Vampiric { fraction: VAMPIRIC_FRACTION }); },
+                EliteAffix::FrozenAura => {
+                    commands.entity(entity).insert(FrozenAura { radius: AURA_RADIUS, slow: AURA_SLOW, interval: AURA_INTERVAL, timer: 0.0 });
+                    // NOTE: Child of the elite so it inherits the elite scale, undone here so the visual matches the radius
+                    let aura = commands.spawn((
+                        SpriteBundle {
+                            sprite: Sprite { color: affix.colour().with_a(0.2), custom_size: Some(Vec2::splat(AURA_RADIUS * 2.0 / SCALE)), ..default() },
+                            transform: Transform::from_xyz(0.0, 0.0, AURA_Z),
+                            ..default()
+                        },
+                        Name::new("Frozen Aura")
+                    )).id();
+                    commands.entity(entity).add_child(aura);
+                },
+                EliteAffix::Shielded => {
+                    // NOTE: Max health is synced from stats later in the frame so the shield is sized from the stat, which already has the elite bonus
+                    let max_health = stats.get_stat(StatType::Health).copied().unwrap_or(health.get_max());
+                    health.add_shield(Shield::new(max_health * SHIELD_FRACTION, entity));
+                    ev_status.send(StatusAppliedEvent { target: entity, source: Some(entity), ability: None, status: StatusKind::Shield, duration: 0.0 });
+                },
+            }
+        }
+    }
+}
+
+fn vampiric_heals(
+    mut evr_damage: EventReader<HealthDamageEvent>,
+    mut vampires: Query<(&Vampiric, &mut Health)>
+) {
+    for damage_event in evr_damage.read() {
+        let Some(source) = damage_event.source else { continue; };
+        if source == damage_event.entity { continue; }
+        let Ok((vampiric, mut health)) = vampires.get_mut(source) else { continue; };
+        if health.is_dead() { continue; }
+        health.heal_from(damage_event.amount * vampiric.fraction, Some(source), None);
+    }
+}
+
+fn frozen_aura_update(
+    time: Res<Time>,
+    mut auras: Query<(Entity, &mut FrozenAura, &Transform, &Health)>,
+    mut player_query: Query<(Entity, &mut Stats, &Transform), With<Player>>,
+    mut ev_status: EventWriter<StatusAppliedEvent>
+) {
+    let Ok((player, mut player_stats, player_transform)) = player_query.get_single_mut() else { return; };
+    for (entity, mut aura, transform, health) in auras.iter_mut() {
+        aura.timer -= time.delta_seconds();
+        if aura.timer > 0.0 || health.is_dead() { continue; }
+        aura.timer = aura.interval;
+        if transform.translation.truncate().distance(player_transform.translation.truncate()) > aura.radius { continue; }
+        if !player_stats.has_modifier(StatType::Speed, entity) {
+            ev_status.send(StatusAppliedEvent {
+                target: player,
+                source: Some(entity),
+                ability: None,
+                status: StatusKind::Slow,
+                duration: AURA_SLOW_DURATION
+            });
+        }
+        player_stats.add_modifier(StatType::Speed, StatModifier::percent(-aura.slow, entity).with_duration(AURA_SLOW_DURATION));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roll_distinct_affixes() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let elite = Elite::roll(&mut rng);
+            assert!((1..=MAX_AFFIXES).contains(&elite.affixes.len()));
+            assert!(elite.affixes.iter().enumerate().all(|(i, affix)| !elite.affixes[i + 1..].contains(affix)));
+        }
+    }
+
+    #[test]
+    fn test_prefix_and_tint() {
+        let elite = Elite { affixes: vec![EliteAffix::Hasted, EliteAffix::FrozenAura] };
+        assert_eq!(elite.prefix(), "Hasted Frozen");
+        assert_eq!(elite.tint(Color::WHITE), Color::rgba(0.6, 0.85, 0.6, 1.0));
+        assert!(elite.has(EliteAffix::FrozenAura) && !elite.has(EliteAffix::Shielded));
+    }
+}
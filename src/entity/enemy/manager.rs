@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::entity::health::HealthDeathEvent;
+
+use super::{Enemy, EnemyType};
+
+const DEFAULT_MAX_ENEMIES: usize = 40;
+
+pub struct EnemyManagerPlugin;
+
+impl Plugin for EnemyManagerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EnemyManager>()
+           .add_systems(PostUpdate, (unregister_dead_enemies, sync_enemy_positions.after(unregister_dead_enemies)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnemyRecord {
+    pub enemy_type: EnemyType,
+    /// Refreshed every frame in `PostUpdate`
+    pub position: Vec2,
+}
+
+/// Every living enemy keyed by entity, with spawn caps and spatial queries for abilities and AI
+#[derive(Resource)]
+pub struct EnemyManager {
+    enemies: HashMap<Entity, EnemyRecord>,
+    counts: HashMap<EnemyType, usize>,
+    /// Spawns that have been promised but haven't appeared yet, e.g. behind a summoning circle
+    pending: HashMap<EnemyType, usize>,
+    pub max_enemies: usize,
+    /// Types without an entry are only limited by `max_enemies`
+    pub type_caps: HashMap<EnemyType, usize>,
+    spawned_total: usize,
+}
+
+impl Default for EnemyManager {
+    fn default() -> Self {
+        EnemyManager {
+            enemies: HashMap::new(),
+            counts: HashMap::new(),
+            pending: HashMap::new(),
+            max_enemies: DEFAULT_MAX_ENEMIES,
+            type_caps: [(EnemyType::Shaman, 3), (EnemyType::Warlord, 1)].into_iter().collect(),
+            spawned_total: 0,
+        }
+    }
+}
+
+impl EnemyManager {
+    pub fn register(&mut self, entity: Entity, enemy_type: EnemyType, position: Vec2) {
+        if self.enemies.insert(entity, EnemyRecord { enemy_type, position }).is_none() {
+            *self.counts.entry(enemy_type).or_default() += 1;
+            self.spawned_total += 1;
+        }
+    }
+
+    pub fn unregister(&mut self, entity: Entity) -> Option<EnemyRecord> {
+        let record = self.enemies.remove(&entity)?;
+        if let Some(count) = self.counts.get_mut(&record.enemy_type) {
+            *count = count.saturating_sub(1);
+        }
+        Some(record)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.enemies.contains_key(&entity)
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&EnemyRecord> {
+        self.enemies.get(&entity)
+    }
+
+    pub fn count(&self) -> usize {
+        self.enemies.len()
+    }
+
+    pub fn count_of(&self, enemy_type: EnemyType) -> usize {
+        self.counts.get(&enemy_type).copied().unwrap_or(0)
+    }
+
+    /// Number of enemies ever registered, used to give each spawn a unique name
+    pub fn spawned_total(&self) -> usize {
+        self.spawned_total
+    }
+
+    /// Holds a place under the caps for a spawn that will be registered later
+    pub fn reserve(&mut self, enemy_type: EnemyType) {
+        *self.pending.entry(enemy_type).or_default() += 1;
+    }
+
+    pub fn release(&mut self, enemy_type: EnemyType) {
+        if let Some(pending) = self.pending.get_mut(&enemy_type) {
+            *pending = pending.saturating_sub(1);
+        }
+    }
+
+    /// False once either the global cap or the type's cap is reached, counting reserved spawns
+    pub fn can_spawn(&self, enemy_type: EnemyType) -> bool {
+        let pending_of = self.pending.get(&enemy_type).copied().unwrap_or(0);
+        let pending_total: usize = self.pending.values().sum();
+        self.count() + pending_total < self.max_enemies && self.type_caps.get(&enemy_type).map_or(true, |cap| self.count_of(enemy_type) + pending_of < *cap)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &EnemyRecord)> {
+        self.enemies.iter().map(|(entity, record)| (*entity, record))
+    }
+
+    /// Closest enemy to `point` and its distance
+    pub fn nearest(&self, point: Vec2) -> Option<(Entity, f32)> {
+        self.nearest_where(point, |_, _| true)
+    }
+
+    pub fn nearest_where(&self, point: Vec2, mut filter: impl FnMut(Entity, &EnemyRecord) -> bool) -> Option<(Entity, f32)> {
+        self.iter()
+            .filter(|(entity, record)| filter(*entity, record))
+            .map(|(entity, record)| (entity, record.position.distance_squared(point)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(entity, distance_squared)| (entity, distance_squared.sqrt()))
+    }
+
+    /// Enemies within `radius` of `point`, closest first
+    pub fn within_radius(&self, point: Vec2, radius: f32) -> Vec<Entity> {
+        let mut found: Vec<(Entity, f32)> = self.iter()
+            .map(|(entity, record)| (entity, record.position.distance_squared(point)))
+            .filter(|(_, distance_squared)| *distance_squared <= radius * radius)
+            .collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        found.into_iter().map(|(entity, _)| entity).collect()
+    }
+}
+
+/// Deaths are the normal way out, anything despawned some other way is caught through `RemovedComponents`
+fn unregister_dead_enemies(
+    mut manager: ResMut<EnemyManager>,
+    mut evr_death: EventReader<HealthDeathEvent>,
+    mut removed: RemovedComponents<Enemy>
+) {
+    for death in evr_death.read() {
+        manager.unregister(death.entity);
+    }
+    for entity in removed.read() {
+        manager.unregister(entity);
+    }
+}
+
+fn sync_enemy_positions(
+    mut manager: ResMut<EnemyManager>,
+    enemies: Query<&Transform, With<Enemy>>
+) {
+    for (entity, record) in manager.enemies.iter_mut() {
+        if let Ok(transform) = enemies.get(*entity) {
+            record.position = transform.translation.truncate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> EnemyManager {
+        let mut manager = EnemyManager::default();
+        manager.register(Entity::from_raw(1), EnemyType::Orc, Vec2::new(10.0, 0.0));
+        manager.register(Entity::from_raw(2), EnemyType::Orc, Vec2::new(50.0, 0.0));
+        manager.register(Entity::from_raw(3), EnemyType::Shaman, Vec2::new(-30.0, 0.0));
+        manager
+    }
+
+    #[test]
+    fn test_register_and_unregister() {
+        let mut manager = manager();
+        assert_eq!(manager.count(), 3);
+        assert_eq!(manager.count_of(EnemyType::Orc), 2);
+        manager.unregister(Entity::from_raw(1));
+        assert_eq!(manager.count_of(EnemyType::Orc), 1);
+        assert!(manager.unregister(Entity::from_raw(1)).is_none());
+        assert_eq!(manager.spawned_total(), 3);
+    }
+
+    #[test]
+    fn test_spatial_queries() {
+        let manager = manager();
+        assert_eq!(manager.nearest(Vec2::ZERO), Some((Entity::from_raw(1), 10.0)));
+        assert_eq!(manager.within_radius(Vec2::ZERO, 40.0), vec![Entity::from_raw(1), Entity::from_raw(3)]);
+        assert_eq!(manager.nearest_where(Vec2::ZERO, |_, record| record.enemy_type == EnemyType::Shaman).map(|(entity, _)| entity), Some(Entity::from_raw(3)));
+    }
+
+    #[test]
+    fn test_caps() {
+        let mut manager = manager();
+        manager.type_caps.insert(EnemyType::Orc, 2);
+        assert!(!manager.can_spawn(EnemyType::Orc));
+        assert!(manager.can_spawn(EnemyType::Archer));
+        manager.max_enemies = 3;
+        assert!(!manager.can_spawn(EnemyType::Archer));
+    }
+
+    #[test]
+    fn test_reservations_count_towards_caps() {
+        let mut manager = manager();
+        manager.type_caps.insert(EnemyType::Archer, 1);
+        manager.reserve(EnemyType::Archer);
+        assert!(!manager.can_spawn(EnemyType::Archer));
+        manager.release(EnemyType::Archer);
+        assert!(manager.can_spawn(EnemyType::Archer));
+    }
+}
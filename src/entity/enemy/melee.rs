@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::abilities::abilities::AutoDestroy;
+use crate::animation::directional_animator::{AnimationType, DirectionalAnimator};
+use crate::entity::{damage::{AttackerSnapshot, DamageType}, health::{DamageInstance, Health}, stats::Stats};
+use crate::player::Player;
+
+use super::orc::Attack;
+use super::state_machine::{StateMachine, Trigger};
+use super::telegraph::spawn_line_telegraph;
+use super::EnemyBehaviour;
+
+const TELEGRAPH_COLOUR: Color = Color::rgb(1.0, 0.3, 0.2);
+/// Used when the archetype has no attack animation to time the swing from
+const FALLBACK_WINDUP: f32 = 0.5;
+const HITBOX_LIFETIME: f32 = 0.1;
+
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MeleeSwing>()
+           .add_systems(Update, (melee_swing_update, melee_hitbox_hits.after(melee_swing_update)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum SwingPhase { WindUp, Recovery }
+
+/// A melee attack in progress, the hitbox only comes out once the attack animation reaches its active frame
+#[derive(Component, Reflect)]
+pub struct MeleeSwing {
+    pub direction: Vec2,
+    pub phase: SwingPhase,
+    /// Time left before the attacker can act again once the hitbox is out
+    pub recovery: f32,
+}
+
+/// Short lived sensor in front of a swinging enemy, hurts the player at most once
+#[derive(Component)]
+pub struct MeleeHitbox {
+    pub damage: f32,
+    pub knockback: Vec2,
+    pub attacker: AttackerSnapshot,
+    pub owner: Entity
+}
+
+/// First atlas index of the attack animation and how long each of its frames lasts
+fn attack_clip(animator: &DirectionalAnimator) -> Option<(usize, usize, f32)> {
+    animator.animation_indices.get(&AnimationType::Attack)
+        .and_then(|clips| clips.get(&animator.direction))
+        .map(|clip| (clip.first, clip.last, clip.frame_length))
+}
+
+/// Telegraphs a swing toward `direction` lasting until the attack animation reaches `active_frame`
+pub fn start_swing(commands: &mut Commands, entity: Entity, origin: Vec2, direction: Vec2, animator: &DirectionalAnimator, behaviour: &EnemyBehaviour) {
+    let EnemyBehaviour::Melee { reach, width, active_frame, recovery, .. } = behaviour else { return; };
+    let windup = attack_clip(animator).map_or(FALLBACK_WINDUP, |(_, _, frame_length)| frame_length * (*active_frame as f32 + 1.0));
+    spawn_line_telegraph(commands, origin, direction, *reach, *width, windup, TELEGRAPH_COLOUR);
+    commands.entity(entity).insert(MeleeSwing { direction, phase: SwingPhase::WindUp, recovery: *recovery });
+}
+
+fn melee_swing_update(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut swings: Query<(Entity, &mut MeleeSwing, &EnemyBehaviour, &DirectionalAnimator, &TextureAtlas, &Transform, &Stats, Option<&mut StateMachine>), With<Attack>>
+) {
+    for (entity, mut swing, behaviour, animator, atlas, transform, stats, machine) in swings.iter_mut() {
+        let EnemyBehaviour::Melee { damage, knockback, reach, width, active_frame, .. } = behaviour else { continue; };
+        match swing.phase {
+            SwingPhase::WindUp => {
+                let active = match attack_clip(animator) {
+                    Some((first, last, _)) => animator.animation == AnimationType::Attack && atlas.index >= first + active_frame && atlas.index <= last,
+                    None => true,
+                };
+                if !active {
+                    continue;
+                }
+                let angle = Vec2::X.angle_between(swing.direction);
+                let centre = transform.translation.truncate() + swing.direction * *reach * 0.5;
+                commands.spawn((
+                    TransformBundle::from_transform(Transform::from_translation(centre.extend(0.0)).with_rotation(Quat::from_rotation_z(angle))),
+                    Collider::cuboid(*reach * 0.5, *width * 0.5),
+                    Sensor,
+                    MeleeHitbox { damage: *damage, knockback: swing.direction * *knockback, attacker: AttackerSnapshot::from_stats(stats), owner: entity },
+                    AutoDestroy::new(HITBOX_LIFETIME),
+                    Name::new("Melee Hitbox")
+                ));
+                swing.phase = SwingPhase::Recovery;
+            },
+            SwingPhase::Recovery => {
+                swing.recovery -= time.delta_seconds();
+                if swing.recovery > 0.0 {
+                    continue;
+                }
+                commands.entity(entity).remove::<MeleeSwing>();
+                if let Some(mut machine) = machine {
+                    machine.signal(Trigger::Finished);
+                }
+            }
+        }
+    }
+}
+
+fn melee_hitbox_hits(
+    mut commands: Commands,
+    rapier: Res<RapierContext>,
+    mut player_query: Query<(Entity, &mut Health), With<Player>>,
+    hitboxes: Query<(Entity, &MeleeHitbox)>
+) {
+    let Ok((player_entity, mut player_health)) = player_query.get_single_mut() else { return; };
+    for (entity, hitbox) in hitboxes.iter() {
+        if rapier.intersection_pair(player_entity, entity).is_none() {
+            continue;
+        }
+        player_health.push(
+            DamageInstance::new(hitbox.damage, DamageType::PHYSICAL, true)
+                .with_attacker(hitbox.attacker)
+                .with_source(hitbox.owner)
+                .with_knockback(hitbox.knockback)
+        );
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,29 +1,68 @@
 use bevy::{ecs::system::EntityCommands, prelude::*};
 use bevy_rapier2d::dynamics::Velocity;
-use crate::{animation::directional_animator::{vec2_to_direction, AnimationType, DirectionalAnimator}, health::{EntityType, Health, HealthDeathEvent}, player::Player};
+use serde::Deserialize;
+use crate::animation::directional_animator::{vec2_to_direction, DirectionalAnimator};
+use crate::abilities::abilities::AbilityType;
+use crate::entity::health::{death_update, health_update};
 
 use self::orc::*;
+use self::archer::Kite;
+use self::caster::Cast;
+use self::charger::{Dash, Stagger, WindUp};
+use self::boss::Pattern;
+use self::perception::Search;
+use self::territory::Return;
 
 pub mod spawner;
 pub mod orc;
 pub mod data;
+pub mod telegraph;
+pub mod archer;
+pub mod caster;
+pub mod charger;
+pub mod boss;
+pub mod state_machine;
+pub mod behaviour_tree;
+pub mod perception;
+pub mod melee;
+pub mod tactics;
+pub mod waves;
+pub mod elite;
+pub mod manager;
+pub mod territory;
 
 #[derive(Component, Reflect)]
 pub struct Enemy {
     pub enemy_type: EnemyType,
+    /// Mirrors `StateMachine::state` for the inspector
     pub enemy_state: EnemyState,
     pub action_timer: Timer,
     pub anim_timer: Timer,
-    pub state_transitions: StateTransitions,
 }
 
-#[derive(Debug, Clone, Reflect)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Reflect, Deserialize)]
 pub enum EnemyState { 
+    #[default]
     Idle, 
     Wander, 
     Chase, 
     Attack, 
-    Death
+    Death,
+    /// Backing away from the player to get back into range
+    Kite,
+    /// Telegraphing then casting an ability
+    Cast,
+    /// Standing still and telegraphing a dash
+    WindUp,
+    Dash,
+    /// Stunned after dashing into a wall
+    Stagger,
+    /// Running the next attack pattern of a boss phase
+    Pattern,
+    /// Checking where the player was last seen or heard
+    Search,
+    /// Walking back home after being pulled out of its `Territory`
+    Return
 }
 
 impl EnemyState {
@@ -34,12 +73,53 @@ impl EnemyState {
             EnemyState::Chase => { commands.entity(entity).insert(Chase); },
             EnemyState::Attack => { commands.entity(entity).insert(Attack); },
             EnemyState::Death => { commands.entity(entity).insert(Death); },
+            EnemyState::Kite => { commands.entity(entity).insert(Kite); },
+            EnemyState::Cast => { commands.entity(entity).insert(Cast::default()); },
+            EnemyState::WindUp => { commands.entity(entity).insert(WindUp); },
+            EnemyState::Dash => { commands.entity(entity).insert(Dash::default()); },
+            EnemyState::Stagger => { commands.entity(entity).insert(Stagger); },
+            EnemyState::Pattern => { commands.entity(entity).insert(Pattern); },
+            EnemyState::Search => { commands.entity(entity).insert(Search::default()); },
+            EnemyState::Return => { commands.entity(entity).insert(Return::default()); },
         }
     }
+
+    pub fn remove(&self, entity: Entity, commands: &mut Commands) {
+        match self {
+            EnemyState::Idle => { commands.entity(entity).remove::<Idle>(); },
+            EnemyState::Wander => { commands.entity(entity).remove::<Wander>(); },
+            EnemyState::Chase => { commands.entity(entity).remove::<Chase>(); },
+            EnemyState::Attack => { commands.entity(entity).remove::<Attack>(); },
+            EnemyState::Death => { commands.entity(entity).remove::<Death>(); },
+            EnemyState::Kite => { commands.entity(entity).remove::<Kite>(); },
+            EnemyState::Cast => { commands.entity(entity).remove::<Cast>(); },
+            EnemyState::WindUp => { commands.entity(entity).remove::<WindUp>(); },
+            EnemyState::Dash => { commands.entity(entity).remove::<Dash>(); },
+            EnemyState::Stagger => { commands.entity(entity).remove::<Stagger>(); },
+            EnemyState::Pattern => { commands.entity(entity).remove::<Pattern>(); },
+            EnemyState::Search => { commands.entity(entity).remove::<Search>(); },
+            EnemyState::Return => { commands.entity(entity).remove::<Return>(); },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Reflect, PartialEq, Eq, Hash, Deserialize)]
+pub enum EnemyType { Orc, Archer, Caster, Charger, Warlord, Shaman }
+
+impl EnemyType {
+    pub const ALL: [EnemyType; 6] = [EnemyType::Orc, EnemyType::Archer, EnemyType::Caster, EnemyType::Charger, EnemyType::Warlord, EnemyType::Shaman];
 }
 
-#[derive(Debug, Clone, Copy, Reflect)]
-pub enum EnemyType { Orc }
+/// How an archetype attacks once `chase_player` fires, tuned per archetype in its definition
+#[derive(Component, Debug, Clone, Reflect, Deserialize)]
+pub enum EnemyBehaviour {
+    /// Swings at `reach` once the attack animation reaches `active_frame`, then recovers for `recovery` seconds
+    Melee { damage: f32, knockback: f32, reach: f32, width: f32, active_frame: usize, recovery: f32 },
+    Ranged { damage: f32, projectile_speed: f32 },
+    /// Casts from its `AbilitySystem`, telegraphing each cast for `telegraph` seconds
+    Caster { abilities: Vec<AbilityType>, telegraph: f32 },
+    Charger { windup: f32, dash_speed: f32, dash_duration: f32, damage: f32, knockback: f32, wall_stun: f32 },
+}
 
 impl Enemy {
     pub fn new(enemy_type: EnemyType) -> Self {
@@ -47,34 +127,6 @@ impl Enemy {
              enemy_state: EnemyState::Idle,
              action_timer: Timer::from_seconds(5.0, TimerMode::Once),
              anim_timer: Timer::from_seconds(0.0, TimerMode::Once),
-             state_transitions: StateTransitions::new(enemy_type) 
-        }
-    }
-}
-
-#[derive(Debug, Clone, Reflect)]
-pub struct StateTransitions {
-    pub idle_exit: EnemyState,
-    pub wander_idle: EnemyState,
-    pub wander_chase: EnemyState,
-    pub chase_exit: EnemyState,
-    pub chase_player: EnemyState,
-    pub attack_finished: EnemyState,
-}
-
-impl StateTransitions {
-    pub fn new(enemy_type: EnemyType) -> StateTransitions {
-        match enemy_type {
-            EnemyType::Orc => {
-                StateTransitions {
-                    idle_exit: EnemyState::Wander,
-                    wander_idle: EnemyState::Idle,
-                    wander_chase: EnemyState::Chase,
-                    chase_exit: EnemyState::Wander,
-                    chase_player: EnemyState::Attack,
-                    attack_finished: EnemyState::Chase,
-                }
-            },
         }
     }
 }
@@ -85,40 +137,42 @@ pub struct EnemySpawnEvent {
     pub enemy_type: EnemyType
 }
 
-pub fn enemy_spawn_init(
-    mut commands: Commands,
-    mut spawned_enemies: EventReader<EnemySpawnEvent>
-) {
-    for spawned_enemy in spawned_enemies.read() {
-        info!("Spawn init!");
-        match spawned_enemy.enemy_type {
-            EnemyType::Orc => { commands.entity(spawned_enemy.entity).insert(orc::Idle); },
-        }
-    }
-}
-
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<spawner::EnemyManager>();
+        app.init_resource::<data::EnemyDefinitions>();
+        app.init_asset::<data::EnemyDefinition>();
+        app.init_asset_loader::<data::EnemyDefinitionLoader>();
         app.add_event::<EnemySpawnEvent>();
-        app.add_systems(Startup, spawn_spawners);
-        app.add_systems(FixedUpdate, (spawner::update_spawners, enemy_spawn_init));
-        app.add_systems(Update, (update_enemy_direction, on_enemy_death));
+        app.add_event::<data::LootDropEvent>();
+        app.add_systems(Startup, data::load_enemy_definitions);
+        app.add_systems(FixedUpdate, spawner::update_spawners);
+        app.add_plugins((
+            orc::EnemyStateMachinePlugin, 
+            archer::ArcherPlugin, 
+            caster::CasterPlugin, 
+            charger::ChargerPlugin, 
+            telegraph::TelegraphPlugin,
+            boss::BossPlugin,
+            state_machine::StateMachinePlugin,
+            behaviour_tree::BehaviourTreePlugin,
+            perception::PerceptionPlugin,
+            melee::MeleePlugin,
+            tactics::TacticsPlugin,
+            waves::WavePlugin,
+            elite::ElitePlugin,
+            manager::EnemyManagerPlugin,
+            territory::TerritoryPlugin
+        ));
+        app.add_systems(Update, (
+            update_enemy_direction, 
+            spawner::resolve_summons,
+            data::drop_loot.after(health_update).before(death_update)
+        ));
     }
 }
 
-pub fn spawn_spawners(mut commands: Commands) {
-    commands.spawn(
-        spawner::EnemySpawner::new(
-            EnemyType::Orc, 
-            1.0, 
-            1, 
-            vec!(Vec2::new(100.0, 10.0))
-        )).insert(Transform::from_xyz(-50.0, 50.0, 1.0));
-}
-
 pub fn update_enemy_direction(
     mut enemies: Query<(&Velocity, &mut DirectionalAnimator)>
 ) {
@@ -129,15 +183,3 @@ pub fn update_enemy_direction(
     }
 }
 
-pub fn on_enemy_death(
-    mut evr_enemy_death: EventReader<HealthDeathEvent>,
-    mut player_q: Query<&mut Health, With<Player>>
-) {
-    let Ok(mut player_health) = player_q.get_single_mut() else { return; };
-    for enemy_death_event in evr_enemy_death.read() {
-        if enemy_death_event.entity_type != EntityType::Player {
-            player_health.heal(10.0);
-            // TODO: Convert this to add xp too!
-        }
-    }
-}
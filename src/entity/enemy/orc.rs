@@ -1,14 +1,20 @@
-use std::time::Duration;
-
 use bevy::{ecs::query::QuerySingleError, prelude::*};
-use crate::pathfinding::{AIPath, Grid};
+use crate::pathfinding::{AIPath, Grid, GridPos, GRID_TOLERANCE};
 use crate::{pathfinding::AITarget, player::Player};
-use crate::entity::{health::Health, damage::DamageType, stats::{Stats, StatType}};
+use crate::entity::{damage::AttackerSnapshot, stats::Stats};
 
 use super::*;
-use rand::Rng;
+use super::archer::spawn_arrow;
+use super::melee::start_swing;
+use super::perception::Perception;
+use super::tactics::AttackSlot;
+use super::territory::{wander_destination, Patrol, Territory};
+
+const WANDER_RADIUS: f32 = 100.0;
+/// How far a blocked destination can be moved to find walkable ground
+pub const DESTINATION_SEARCH_DISTANCE: f32 = 32.0;
 
-fn get_player_pos(player_transform: Result<&Transform, QuerySingleError>) -> Option<Vec2> {
+pub fn get_player_pos(player_transform: Result<&Transform, QuerySingleError>) -> Option<Vec2> {
     match player_transform {
         Ok(transform) => Some(transform.translation.truncate()),
         Err(_) => None,
@@ -26,173 +32,86 @@ pub struct Attack;
 #[derive(Component, Reflect, Debug, Clone)]
 pub struct Death;
 
+/// Behaviour for the shared states, when to change state lives in each definition's `transitions`
 pub struct EnemyStateMachinePlugin;
 
 impl Plugin for EnemyStateMachinePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, (
-            idle_enter.before(idle_update),
-            idle_update,
-            wander_enter.before(wander_update),
-            wander_update,
-            chase_enter.before(chase_update),
+            wander_enter,
             chase_update,
-            attack_enter.before(attack_update),
-            attack_update,
+            attack_enter,
         ));
     }
 }
 
-fn idle_enter(
-    mut enemies: Query<(&mut Enemy, &mut DirectionalAnimator, &mut AITarget), Added<Idle>>
-) {
-    let mut rng = rand::thread_rng();
-    for (mut enemy, mut anim, mut ai) in enemies.iter_mut() {
-        enemy.enemy_state = EnemyState::Idle;
-        enemy.action_timer = Timer::from_seconds(rng.gen_range(3.0..5.0), TimerMode::Once);
-        anim.update_animation(AnimationType::Idle);
-        ai.do_path_find = false;
-        info!("Started orc idle with time {:?}", enemy.action_timer.duration());
-    }
+/// Random reachable cell near `position`, shared by the `Wander` state and behaviour trees
+pub fn pick_wander_destination(grid: &Grid, position: Vec2) -> Option<GridPos> {
+    grid.random_reachable_point(position, WANDER_RADIUS, &mut rand::thread_rng())
 }
 
-fn idle_update(
-    time: Res<Time>,
-    mut commands: Commands,
-    mut orcs: Query<(Entity, &mut Enemy), With<Idle>>
-) {
-    for (entity, mut enemy) in orcs.iter_mut() {
-        enemy.action_timer.tick(Duration::from_secs_f32(time.delta_seconds()));
-        if enemy.action_timer.finished() {
-            commands.entity(entity).remove::<Idle>();
-        }
+/// Points `ai` at `target` if it has moved away from its destination, returns true if the current path should be dropped
+pub fn update_chase_destination(grid: &Grid, target: Vec2, ai: &mut AITarget) -> bool {
+    if grid.grid_to_world(ai.destination).distance_squared(target) < GRID_TOLERANCE.powi(2) {
+        return false;
     }
+    let Some(destination) = grid.nearest_walkable(target, DESTINATION_SEARCH_DISTANCE) else { return false; };
+    ai.destination = destination;
+    true
 }
 
 fn wander_enter(
     mut commands: Commands,
-    mut orcs: Query<(Entity, &mut Enemy, &mut DirectionalAnimator, &mut AITarget, &Transform, Option<&AIPath>), Added<Wander>>,
+    mut orcs: Query<(Entity, &mut AITarget, &Transform, Option<&AIPath>, Option<&Territory>, Option<&mut Patrol>), Added<Wander>>,
     grid: Res<Grid>
 ) {
-    let mut rng = rand::thread_rng();
-    for (entity, mut enemy, mut anim, mut ai, transform, path) in orcs.iter_mut() {
-        ai.do_path_find = true;
-        let angle = rng.gen_range(-2.0 * std::f32::consts::PI..2.0 * std::f32::consts::PI).to_radians();
-        let pos = Vec2::new(transform.translation.x + angle.cos() * 100.0, transform.translation.y.sin() * 100.0);
-        if let Some(grid_pos) = grid.sample_position(&(transform.translation.truncate() + pos).as_ivec2(), (pos - transform.translation.truncate()).normalize()) {
+    for (entity, mut ai, transform, path, territory, patrol) in orcs.iter_mut() {
+        if let Some(destination) = wander_destination(&grid, transform.translation.truncate(), territory, patrol.map(|patrol| patrol.into_inner())) {
             if path.is_some() {
                 commands.entity(entity).remove::<AIPath>();
             }
-            ai.destination = Vec2::new(grid_pos.0 as f32, grid_pos.1 as f32);
-            info!("Intialised orc with wander target: ({}, {})", grid_pos.0, grid_pos.1);
+            ai.destination = destination;
+            info!("Intialised orc with wander target: ({}, {})", destination.x, destination.y);
         }
-        enemy.enemy_state = EnemyState::Wander;
-        anim.update_animation(AnimationType::Walk);
-    }
-}
-
-fn wander_update(
-    grid: Res<Grid>,
-    mut commands: Commands,
-    player_query: Query<&Transform, With<Player>>,
-    mut orcs: Query<(Entity, &Enemy, &Transform, &AITarget, Option<&AIPath>), With<Wander>>
-) {
-    let Some(player_pos) = get_player_pos(player_query.get_single()) else {
-        for (en, _, _, _, _) in orcs.iter() {
-            commands.entity(en).remove::<Wander>().insert(Idle);
-        }
-        return;
-    };
-    for (entity, enemy, transform, ai, path) in orcs.iter_mut() {
-        if player_pos.distance_squared(transform.translation.truncate()) <= ai.follow_range.powi(2) {
-            info!("Player in range!");
-            if path.is_some() {
-                commands.entity(entity).remove::<AIPath>();
-            }
-            enemy.state_transitions.wander_chase.clone().spawn(entity, &mut commands);
-            commands.entity(entity).remove::<Wander>();
-        }
-        if transform.translation.truncate().distance_squared(grid.grid_to_world_coords(&ai.destination.as_ivec2())) <= crate::pathfinding::GRID_TOLERANCE.powi(2) {
-            if path.is_some() {
-                commands.entity(entity).remove::<AIPath>();
-            }
-            enemy.state_transitions.wander_idle.clone().spawn(entity, &mut commands);
-            commands.entity(entity).remove::<Wander>(); 
-            continue;
-        }
-    }
-}
-
-fn chase_enter(
-    mut anims: Query<(&mut DirectionalAnimator, &mut AITarget), Added<Chase>>
-) {
-    for (mut anim, mut ai) in anims.iter_mut() {
-        ai.do_path_find = true;
-        anim.update_animation(AnimationType::Walk);
     }
 }
 
+/// Keeps the path pointed at the player or the slot the coordinator gave us, or where they were last seen once out of sight
 fn chase_update(
     grid: Res<Grid>,
     mut commands: Commands,
     player_query: Query<&Transform, With<Player>>,
-    mut orcs: Query<(Entity, &Enemy, &Transform, &mut AITarget, Option<&AIPath>), With<Chase>>
+    mut orcs: Query<(Entity, &Transform, &mut AITarget, Option<&Perception>, Option<&AttackSlot>), With<Chase>>
 ) {
-    let Some(player_pos) = get_player_pos(player_query.get_single()) else {
-        for (entity, _, _, _, _) in orcs.iter() {
-            commands.entity(entity).remove::<Chase>().insert(Idle);
-        }
-        return;
-    };
-    for (entity, enemy, transform, mut ai, path) in orcs.iter_mut() {
-        let distance_to_player = transform.translation.truncate().distance(player_pos);
-        if distance_to_player >= ai.follow_range {
-            if path.is_some() {
-                enemy.state_transitions.chase_exit.clone().spawn(entity, &mut commands);
-                commands.entity(entity).remove::<Chase>();
-            }
-            continue;
-        }
-        if grid.grid_to_world_coords(&ai.destination.as_ivec2()).distance_squared(player_pos) >= crate::pathfinding::GRID_TOLERANCE.powi(2) {
-            if let Some((pos_x, pos_y)) = grid.sample_position(&player_pos.as_ivec2(), (player_pos - transform.translation.truncate()).normalize()) {
-                commands.entity(entity).remove::<AIPath>();
-                ai.destination = Vec2::new(pos_x as f32, pos_y as f32);
-            } 
-        }
-        if distance_to_player <= ai.attack_range {
-            enemy.state_transitions.chase_player.clone().spawn(entity, &mut commands);
-            commands.entity(entity).remove::<Chase>();
+    let Some(player_pos) = get_player_pos(player_query.get_single()) else { return; };
+    for (entity, transform, mut ai, perception, slot) in orcs.iter_mut() {
+        let target = match perception {
+            Some(perception) if !perception.can_see_player => perception.last_known_position.unwrap_or(player_pos),
+            _ => slot.map_or(player_pos, |slot| slot.position)
+        };
+        if update_chase_destination(&grid, target, &mut ai) {
+            commands.entity(entity).remove::<AIPath>();
         }
     }
 }
 
 fn attack_enter(
-    mut player_query: Query<&mut Health, With<Player>>,
-    mut orcs: Query<(&mut Enemy, &mut DirectionalAnimator, &Stats), Added<Attack>>
-) {
-    let Some(mut player_health) = (match player_query.get_single_mut() {
-        Ok(health) => Some(health),
-        Err(_) => None,
-    }) else { return; };
-    for (mut enemy, mut animator, stats) in orcs.iter_mut() {
-        animator.update_animation(AnimationType::Attack);
-        enemy.enemy_state = EnemyState::Attack;
-        enemy.action_timer = Timer::from_seconds(1.0, TimerMode::Once);
-        let Some(damage) = stats.get_stat(StatType::Attack) else { continue; };
-        player_health.push_damage(*damage, DamageType::PHYSICAL);
-    }
-}
-
-fn attack_update(
-    time: Res<Time>,
     mut commands: Commands,
-    mut orcs: Query<(Entity, &mut Enemy), With<Attack>>
+    player_query: Query<&Transform, With<Player>>,
+    orcs: Query<(Entity, &EnemyBehaviour, &Stats, &Transform, &DirectionalAnimator), (Added<Attack>, Without<Player>)>
 ) {
-    for (entity, mut enemy) in orcs.iter_mut() {
-        enemy.action_timer.tick(Duration::from_secs_f32(time.delta_seconds()));
-        if enemy.action_timer.finished() {
-            enemy.state_transitions.attack_finished.clone().spawn(entity, &mut commands);
-            commands.entity(entity).remove::<Attack>();
+    let Some(player_pos) = get_player_pos(player_query.get_single()) else { return; };
+    for (entity, behaviour, stats, transform, animator) in orcs.iter() {
+        let direction = (player_pos - transform.translation.truncate()).normalize_or_zero();
+        match behaviour {
+            EnemyBehaviour::Ranged { damage, projectile_speed } => {
+                spawn_arrow(&mut commands, entity, transform.translation.truncate(), direction, *projectile_speed, *damage, AttackerSnapshot::from_stats(stats));
+            },
+            EnemyBehaviour::Melee { .. } => {
+                start_swing(&mut commands, entity, transform.translation.truncate(), direction, animator, behaviour);
+            },
+            // NOTE: Casters and chargers attack from their own states, `Attack` just plays the animation for them
+            _ => {}
         }
     }
 }
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::abilities::abilities::AbilityCastEvent;
+use crate::entity::health::HealthDamageEvent;
+use crate::map::Wall;
+use crate::pathfinding::{AIPath, AITarget, Grid, GRID_TOLERANCE};
+use crate::player::Player;
+
+use super::orc::{pick_wander_destination, DESTINATION_SEARCH_DISTANCE};
+
+/// How far a spell cast carries compared to an enemy's hearing radius
+const CAST_LOUDNESS: f32 = 1.0;
+const IMPACT_LOUDNESS: f32 = 0.6;
+
+pub struct PerceptionPlugin;
+
+impl Plugin for PerceptionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Perception>()
+           .register_type::<Search>()
+           .add_event::<NoiseEvent>()
+           .add_systems(Update, (
+               emit_noises,
+               update_perception,
+               hear_noises.after(emit_noises).after(update_perception),
+               search_enter.after(hear_noises),
+               search_update.after(search_enter)
+           ));
+    }
+}
+
+/// Something enemies can hear, `loudness` scales each enemy's hearing radius
+#[derive(Event)]
+pub struct NoiseEvent {
+    pub position: Vec2,
+    pub loudness: f32
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct PerceptionDefinition {
+    /// Defaults to the follow range
+    #[serde(default)]
+    pub view_distance: Option<f32>,
+    /// Full width of the vision cone in degrees
+    pub view_angle: f32,
+    pub hearing_radius: f32,
+    pub awareness_radius: f32,
+}
+
+impl Default for PerceptionDefinition {
+    fn default() -> Self {
+        PerceptionDefinition { view_distance: None, view_angle: 120.0, hearing_radius: 200.0, awareness_radius: 48.0 }
+    }
+}
+
+/// What an enemy knows about the player, read by the `CanSeePlayer`, `LostPlayer` and `HeardNoise` guards
+#[derive(Component, Reflect)]
+pub struct Perception {
+    pub view_distance: f32,
+    /// Full width of the vision cone in degrees
+    pub view_angle: f32,
+    pub hearing_radius: f32,
+    /// The player is noticed this close whichever way the enemy faces
+    pub awareness_radius: f32,
+    pub can_see_player: bool,
+    /// Where the player was last seen or heard
+    pub last_known_position: Option<Vec2>,
+    pub time_since_seen: f32,
+    /// Set when a noise is heard, cleared once the enemy starts searching
+    pub heard_noise: bool,
+    pub facing: Vec2,
+}
+
+impl Perception {
+    pub fn new(definition: &PerceptionDefinition, follow_range: f32) -> Self {
+        Perception {
+            view_distance: definition.view_distance.unwrap_or(follow_range),
+            view_angle: definition.view_angle,
+            hearing_radius: definition.hearing_radius,
+            awareness_radius: definition.awareness_radius,
+            can_see_player: false,
+            last_known_position: None,
+            time_since_seen: f32::INFINITY,
+            heard_noise: false,
+            facing: Vec2::NEG_Y,
+        }
+    }
+
+    fn in_view(&self, offset: Vec2) -> bool {
+        let distance = offset.length();
+        if distance <= self.awareness_radius {
+            return true;
+        }
+        distance <= self.view_distance && self.facing.angle_between(offset).abs() <= (self.view_angle * 0.5).to_radians()
+    }
+}
+
+/// Walks to the last known position then looks around it until the state's timer runs out
+#[derive(Component, Reflect, Default)]
+pub struct Search {
+    pub origin: Option<Vec2>,
+}
+
+fn emit_noises(
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    mut ev_cast: EventReader<AbilityCastEvent>,
+    mut ev_damage: EventReader<HealthDamageEvent>,
+    mut ev_noise: EventWriter<NoiseEvent>
+) {
+    let player = player_query.get_single().ok();
+    for cast in ev_cast.read() {
+        let Some((_, transform)) = player.filter(|(entity, _)| *entity == cast.caster) else { continue; };
+        ev_noise.send(NoiseEvent { position: transform.translation.truncate(), loudness: CAST_LOUDNESS });
+    }
+    for damage in ev_damage.read() {
+        if damage.is_dot {
+            continue;
+        }
+        ev_noise.send(NoiseEvent { position: damage.pos, loudness: IMPACT_LOUDNESS });
+    }
+}
+
+fn update_perception(
+    time: Res<Time>,
+    rapier: Res<RapierContext>,
+    walls: Query<(), With<Wall>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut enemies: Query<(Entity, &mut Perception, &Transform, &Velocity), Without<Player>>
+) {
+    let player_pos = player_query.get_single().ok().map(|transform| transform.translation.truncate());
+    for (entity, mut perception, transform, velocity) in enemies.iter_mut() {
+        if velocity.linvel.length_squared() >= 0.01 {
+            perception.facing = velocity.linvel.normalize();
+        }
+        let position = transform.translation.truncate();
+        let sees = player_pos.is_some_and(|player_pos| {
+            let offset = player_pos - position;
+            if !perception.in_view(offset) {
+                return false;
+            }
+            let wall_filter = |collider: Entity| collider != entity && walls.contains(collider);
+            let filter = QueryFilter::default().exclude_sensors().predicate(&wall_filter);
+            rapier.cast_ray(position, offset.normalize_or_zero(), offset.length(), true, filter).is_none()
+        });
+        perception.can_see_player = sees;
+        if sees {
+            perception.time_since_seen = 0.0;
+            perception.last_known_position = player_pos;
+            perception.facing = (player_pos.unwrap_or(position) - position).try_normalize().unwrap_or(perception.facing);
+        } else {
+            perception.time_since_seen += time.delta_seconds();
+        }
+    }
+}
+
+fn hear_noises(
+    mut ev_noise: EventReader<NoiseEvent>,
+    mut enemies: Query<(&mut Perception, &Transform)>
+) {
+    for noise in ev_noise.read() {
+        for (mut perception, transform) in enemies.iter_mut() {
+            if perception.can_see_player || transform.translation.truncate().distance(noise.position) > perception.hearing_radius * noise.loudness {
+                continue;
+            }
+            perception.last_known_position = Some(noise.position);
+            perception.heard_noise = true;
+        }
+    }
+}
+
+fn search_enter(
+    grid: Res<Grid>,
+    mut commands: Commands,
+    mut enemies: Query<(Entity, &mut Search, &mut Perception, &mut AITarget), Added<Search>>
+) {
+    for (entity, mut search, mut perception, mut ai) in enemies.iter_mut() {
+        perception.heard_noise = false;
+        search.origin = perception.last_known_position.take();
+        let Some(origin) = search.origin else { continue; };
+        if let Some(destination) = grid.nearest_walkable(origin, DESTINATION_SEARCH_DISTANCE) {
+            ai.destination = destination;
+            commands.entity(entity).remove::<AIPath>();
+        }
+    }
+}
+
+/// Follows up on anything new heard while searching, otherwise picks spots around the origin
+fn search_update(
+    grid: Res<Grid>,
+    mut commands: Commands,
+    mut enemies: Query<(Entity, &mut Search, &mut Perception, &mut AITarget, &Transform)>
+) {
+    for (entity, mut search, mut perception, mut ai, transform) in enemies.iter_mut() {
+        let position = transform.translation.truncate();
+        if perception.heard_noise {
+            perception.heard_noise = false;
+            let Some(heard) = perception.last_known_position.take() else { continue; };
+            search.origin = Some(heard);
+            if let Some(destination) = grid.nearest_walkable(heard, DESTINATION_SEARCH_DISTANCE) {
+                ai.destination = destination;
+                commands.entity(entity).remove::<AIPath>();
+            }
+            continue;
+        }
+        let at_destination = position.distance_squared(grid.grid_to_world(ai.destination)) <= GRID_TOLERANCE.powi(2);
+        let Some(origin) = search.origin.filter(|_| at_destination) else { continue; };
+        if let Some(destination) = pick_wander_destination(&grid, origin) {
+            ai.destination = destination;
+            commands.entity(entity).remove::<AIPath>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perception() -> Perception {
+        Perception::new(&PerceptionDefinition { view_distance: Some(300.0), view_angle: 120.0, hearing_radius: 200.0, awareness_radius: 48.0 }, 256.0)
+    }
+
+    /// `distance` away from the enemy, `degrees` off where it is facing
+    fn offset(perception: &Perception, degrees: f32, distance: f32) -> Vec2 {
+        Vec2::from_angle(degrees.to_radians()).rotate(perception.facing) * distance
+    }
+
+    #[test]
+    fn test_awareness_radius_ignores_facing() {
+        let perception = perception();
+        assert!(perception.in_view(offset(&perception, 180.0, 40.0)));
+        assert!(!perception.in_view(offset(&perception, 180.0, 60.0)));
+    }
+
+    #[test]
+    fn test_vision_cone() {
+        let perception = perception();
+        assert!(perception.in_view(offset(&perception, 0.0, 200.0)));
+        assert!(perception.in_view(offset(&perception, 59.0, 200.0)));
+        assert!(perception.in_view(offset(&perception, -59.0, 200.0)));
+        assert!(!perception.in_view(offset(&perception, 61.0, 200.0)));
+        assert!(!perception.in_view(offset(&perception, -61.0, 200.0)));
+        assert!(!perception.in_view(offset(&perception, 0.0, 310.0)));
+    }
+}
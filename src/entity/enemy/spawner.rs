@@ -1,9 +1,32 @@
 use std::time::Duration;
 
-use rand::Rng;
+use rand::{seq::SliceRandom, Rng};
 
-use bevy::prelude::*;
-use crate::{ui::healthbar::HealthBarBundle, enemy::*, pathfinding::AITarget};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use crate::{ui::healthbar::HealthBarBundle, enemy::*, entity::{experience::ExperienceReward, hit_reaction::HitReaction}, pathfinding::AITarget};
+use crate::abilities::abilities::AbilitySystem;
+use crate::entity::health::{EntityType, Health};
+use super::behaviour_tree::BehaviourTree;
+use super::boss::Boss;
+use super::perception::Perception;
+use super::tactics::Coordinated;
+use super::territory::Territory;
+use super::elite::Elite;
+use super::waves::WaveMember;
+use super::data::{EnemyDefinition, EnemyDefinitions, LootTable};
+use super::manager::EnemyManager;
+use crate::pathfinding::Grid;
+use crate::player::Player;
+
+const MIN_PLAYER_DISTANCE: f32 = 128.0;
+/// How far a blocked spawn point can be moved to find walkable ground
+const SPAWN_SEARCH_DISTANCE: f32 = 96.0;
+const SUMMON_TIME: f32 = 1.2;
+const SUMMON_SIZE: f32 = 48.0;
+/// Radians per second
+const SUMMON_SPIN: f32 = 3.0;
+const SUMMON_COLOUR: Color = Color::rgb(0.6, 0.2, 0.9);
+const SUMMON_ELITE_COLOUR: Color = Color::rgb(1.0, 0.75, 0.1);
 
 
 
@@ -16,93 +39,218 @@ pub struct EnemySpawner {
     pub spawn_delay: f32,
     pub spawn_count: usize,
     pub max_spawns: usize,
-    pub spawn_points: Vec<Vec2>
+    pub spawn_points: Vec<Vec2>,
+    /// Chance in `[0, 1]` for each spawn to be an `Elite`
+    pub elite_chance: f32
 }
 
 impl EnemySpawner {
     pub fn new(enemy_type: EnemyType, spawn_delay: f32, max_spawns: usize, spawn_points: Vec<Vec2>) -> Self {
-        EnemySpawner { enemy_type, spawn_delay, spawn_timer: Timer::from_seconds(spawn_delay, TimerMode::Repeating), spawn_count: 0, max_spawns, spawn_points }
+        EnemySpawner { enemy_type, spawn_delay, spawn_timer: Timer::from_seconds(spawn_delay, TimerMode::Repeating), spawn_count: 0, max_spawns, spawn_points, elite_chance: 0.0 }
+    }
+
+    pub fn with_elite_chance(mut self, elite_chance: f32) -> Self {
+        self.elite_chance = elite_chance;
+        self
+    }
+}
+
+/// Everything needed to spawn an enemy from its definition outside of a spawner, e.g. boss summons
+#[derive(SystemParam)]
+pub struct EnemySpawning<'w> {
+    assets: Res<'w, AssetServer>,
+    definitions: ResMut<'w, EnemyDefinitions>,
+    definition_assets: Res<'w, Assets<EnemyDefinition>>,
+    atlases: ResMut<'w, Assets<TextureAtlasLayout>>,
+    enemies: ResMut<'w, EnemyManager>,
+    spawn_event: EventWriter<'w, EnemySpawnEvent>,
+}
+
+impl EnemySpawning<'_> {
+    pub fn is_loaded(&self, enemy_type: EnemyType) -> bool {
+        self.definitions.get(enemy_type, &self.definition_assets).is_some()
+    }
+
+    /// Spawners respect the `EnemyManager` caps, direct spawns like boss summons don't
+    pub fn can_spawn(&self, enemy_type: EnemyType) -> bool {
+        self.enemies.can_spawn(enemy_type)
+    }
+
+    /// Holds a place under the caps until a summon resolves, see `resolve_summons`
+    pub fn reserve(&mut self, enemy_type: EnemyType) {
+        self.enemies.reserve(enemy_type);
+    }
+
+    pub fn release(&mut self, enemy_type: EnemyType) {
+        self.enemies.release(enemy_type);
+    }
+
+    /// Spawns and registers an enemy, returning `None` while its definition is still loading
+    pub fn spawn(&mut self, commands: &mut Commands, enemy_type: EnemyType, position: Vec2) -> Option<Entity> {
+        let definition = self.definitions.get(enemy_type, &self.definition_assets)?;
+        let layout = self.definitions.layout(definition, &mut self.atlases);
+        let enemy = spawn_enemy(commands, definition, layout, &self.assets, position, self.enemies.spawned_total());
+        self.enemies.register(enemy, enemy_type, position);
+        self.spawn_event.send(EnemySpawnEvent { entity: enemy, enemy_type });
+        Some(enemy)
     }
 }
 
-impl Default for EnemyManager {
-    fn default() -> Self {
-        EnemyManager { enemies: Vec::new() }
+/// Moves `point` out of rocks onto the nearest walkable spot, `None` if there isn't one nearby or it is too close to the player
+pub fn validate_spawn_point(grid: &Grid, point: Vec2, player_pos: Option<Vec2>) -> Option<Vec2> {
+    let position = grid.grid_to_world(grid.nearest_walkable(point, SPAWN_SEARCH_DISTANCE)?);
+    match player_pos {
+        Some(player_pos) if position.distance(player_pos) < MIN_PLAYER_DISTANCE => None,
+        _ => Some(position)
     }
 }
 
-#[derive(Resource)]
-pub struct EnemyManager {
-    pub enemies: Vec<u32>
+/// Summoning circle played where a spawner's enemy is about to appear, nothing can hurt it until it resolves
+#[derive(Component)]
+pub struct Summoning {
+    pub enemy_type: EnemyType,
+    pub remaining: f32,
+    pub elite: bool
+}
+
+fn spawn_summoning(commands: &mut Commands, enemy_type: EnemyType, position: Vec2, elite: bool, wave: Option<&WaveMember>) {
+    let colour = if elite { SUMMON_ELITE_COLOUR } else { SUMMON_COLOUR };
+    let summoning = commands.spawn((
+        SpriteBundle {
+            sprite: Sprite { color: colour.with_a(0.0), custom_size: Some(Vec2::splat(SUMMON_SIZE)), ..default() },
+            transform: Transform::from_translation(position.extend(-1.5)).with_scale(Vec3::splat(0.25)),
+            ..default()
+        },
+        Summoning { enemy_type, remaining: SUMMON_TIME, elite },
+        Name::new("Summoning Circle")
+    )).id();
+    if let Some(wave) = wave {
+        commands.entity(summoning).insert(wave.clone());
+    }
 }
 
 pub fn update_spawners(
     time: Res<Time>,
-    mut enemies: ResMut<EnemyManager>,
-    mut spawners: Query<(&mut EnemySpawner, Entity)>,
-    mut spawn_event: EventWriter<EnemySpawnEvent>,
+    grid: Res<Grid>,
+    player_query: Query<&Transform, With<Player>>,
+    mut spawners: Query<(&mut EnemySpawner, Entity, Option<&WaveMember>)>,
     mut commands: Commands,
-    assets: Res<AssetServer>,
-    mut atlases: ResMut<Assets<TextureAtlasLayout>>
-
+    mut spawning: EnemySpawning
 ) {
-    for (mut spawner, entity) in spawners.iter_mut() {
+    let player_pos = player_query.get_single().ok().map(|transform| transform.translation.truncate());
+    let mut rng = rand::thread_rng();
+    for (mut spawner, entity, wave) in spawners.iter_mut() {
         if spawner.spawn_count == spawner.max_spawns {
             commands.entity(entity).despawn();
-            return;
+            continue;
         }
+        // NOTE: Definitions load asynchronously so spawners wait for them rather than spawning nothing
+        if !spawning.is_loaded(spawner.enemy_type) { continue; }
+        // NOTE: At the cap the timer is held so the next spawn comes as soon as there is room
+        if !spawning.can_spawn(spawner.enemy_type) { continue; }
         spawner.spawn_timer.tick(Duration::from_secs_f32(time.delta_seconds()));
         if spawner.spawn_timer.just_finished() {
-            let enemy = Enemy::new(spawner.enemy_type);
-            let position_index = rand::thread_rng().gen_range(0..spawner.spawn_points.len());
-            // NOTE: All bevy_hanabi particles are not z sorted so all entities that go infront of particles must be on negative z positions!
-            let enemy_transform = Transform::from_xyz(spawner.spawn_points[position_index].x, spawner.spawn_points[position_index].y, -1.0);
-            match spawner.enemy_type {
-                EnemyType::Orc => {
-                    let orc = data::orc_data();
-                    let texture_handle: Handle<Image> = assets.load(orc.sprite_data.path);
-                    let layout = TextureAtlasLayout::from_grid(
-                        orc.sprite_data.tile_size, 
-                        orc.sprite_data.columns, 
-                        orc.sprite_data.rows, 
-                        orc.sprite_data.padding, 
-                        orc.sprite_data.offset
-                    );
-                    let sprite_bundle: SpriteSheetBundle = SpriteSheetBundle { 
-                        texture: texture_handle,
-                        atlas: TextureAtlas {
-                            layout: atlases.add(layout),
-                            index: 0
-                        },
-                        transform: enemy_transform,
-                        ..default()
-                    };
-
-                    let animator = orc.animator;
-                    let health = orc.health;
-                    let stats = orc.stats;
-                    let enemy = commands.spawn(enemy)
-                        .insert(sprite_bundle)
-                        .insert(animator)
-                        .insert(health)
-                        .insert(stats)
-                        .insert(Collider::ball(16.0))
-                        .insert(RigidBody::Dynamic)
-                        .insert(Velocity::default())
-                        .insert(LockedAxes::ROTATION_LOCKED)
-                        .insert(AITarget::new(256.0, 16.0, false))
-                        .insert(Sensor)
-                        .insert(Enemy::new(EnemyType::Orc))
-                        .insert(Name::new(format!("Orc {}", spawner.spawn_count)))
-                    .id();
-                    let health_bar = commands.spawn(HealthBarBundle::new(data::orc_data().health.get_max(), assets.load("ui/health_bar.png"), Vec2::new(0.0, 32.0))).id();
-                    commands.entity(enemy).push_children(&[health_bar]);
-                    enemies.enemies.push(enemy.index());
-                    spawn_event.send(EnemySpawnEvent { entity: enemy, enemy_type: EnemyType::Orc });
-                    info!("Sent event");
-                },
-            }
+            // NOTE: Points are tried in a random order so a blocked point doesn't stall the spawner
+            let mut spawn_points = spawner.spawn_points.clone();
+            spawn_points.shuffle(&mut rng);
+            let Some(position) = spawn_points.into_iter().find_map(|point| validate_spawn_point(&grid, point, player_pos)) else { continue; };
+            let elite = rng.gen_bool(spawner.elite_chance.clamp(0.0, 1.0) as f64);
+            spawning.reserve(spawner.enemy_type);
+            spawn_summoning(&mut commands, spawner.enemy_type, position, elite, wave);
             spawner.spawn_count += 1;
         };
     }
 }
+
+/// Grows and spins summoning circles, then swaps them for the enemy
+pub fn resolve_summons(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut summons: Query<(Entity, &mut Summoning, &mut Transform, &mut Sprite, Option<&WaveMember>)>,
+    mut spawning: EnemySpawning
+) {
+    for (entity, mut summoning, mut transform, mut sprite, wave) in summons.iter_mut() {
+        summoning.remaining -= time.delta_seconds();
+        if summoning.remaining > 0.0 {
+            let progress = 1.0 - (summoning.remaining / SUMMON_TIME).clamp(0.0, 1.0);
+            transform.scale = Vec3::splat(0.25 + 0.75 * progress);
+            transform.rotate_z(SUMMON_SPIN * time.delta_seconds());
+            sprite.color.set_a(0.75 * progress);
+            continue;
+        }
+        commands.entity(entity).despawn_recursive();
+        spawning.release(summoning.enemy_type);
+        let Some(enemy) = spawning.spawn(&mut commands, summoning.enemy_type, transform.translation.truncate()) else { continue; };
+        if let Some(wave) = wave {
+            commands.entity(enemy).insert(wave.clone());
+        }
+        if summoning.elite {
+            commands.entity(enemy).insert(Elite::roll(&mut rand::thread_rng()));
+        }
+    }
+}
+
+/// The one place enemies are assembled, everything archetype specific comes from the definition
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    definition: &EnemyDefinition,
+    layout: Handle<TextureAtlasLayout>,
+    assets: &AssetServer,
+    position: Vec2,
+    index: usize
+) -> Entity {
+    // NOTE: All bevy_hanabi particles are not z sorted so all entities that go infront of particles must be on negative z positions!
+    let sprite_bundle = SpriteSheetBundle { 
+        texture: assets.load(definition.sprite.path.clone()),
+        atlas: TextureAtlas { layout, index: 0 },
+        sprite: Sprite { color: definition.colour(), ..default() },
+        transform: Transform::from_xyz(position.x, position.y, -1.0),
+        ..default()
+    };
+    let stats = definition.stats();
+    let entity_type = if definition.boss.is_some() { EntityType::Boss } else { EntityType::Enemy };
+    let health = Health::from_stats(&stats, entity_type).with_invulnerability(definition.invulnerability);
+    let health_percent = health.get_percent();
+    let mut hit_reaction = HitReaction::new(definition.hit_reaction.stun_duration, definition.hit_reaction.knockback_multiplier);
+    hit_reaction.base_colour = definition.colour();
+    let enemy = commands.spawn(Enemy::new(definition.enemy_type))
+        .insert(sprite_bundle)
+        .insert(definition.animator())
+        .insert(health)
+        .insert(stats)
+        .insert(ExperienceReward { amount: definition.xp })
+        .insert(definition.collider())
+        .insert(RigidBody::Dynamic)
+        .insert(Velocity::default())
+        .insert(Damping { linear_damping: definition.ai.damping, angular_damping: 0.0 })
+        .insert(hit_reaction)
+        .insert(definition.behaviour.clone())
+        .insert(LockedAxes::ROTATION_LOCKED)
+        .insert(AITarget::new(definition.ai.follow_range, definition.ai.attack_range, false))
+        .insert(Perception::new(&definition.perception, definition.ai.follow_range))
+        .insert(Sensor)
+        .insert(Name::new(format!("{} {}", definition.name, index)))
+    .id();
+    match &definition.behaviour_tree {
+        Some(nodes) => { commands.entity(enemy).insert(BehaviourTree::new(nodes.clone())); },
+        None => { commands.entity(enemy).insert(definition.state_machine()); },
+    }
+    if definition.ai.coordinated {
+        commands.entity(enemy).insert(Coordinated);
+    }
+    if let Some(territory) = &definition.territory {
+        commands.entity(enemy).insert(Territory::new(territory, position));
+    }
+    if let EnemyBehaviour::Caster { abilities, .. } = &definition.behaviour {
+        commands.entity(enemy).insert(AbilitySystem::with_abilities(abilities));
+    }
+    if let Some(boss) = &definition.boss {
+        commands.entity(enemy).insert((Boss::new(boss), AbilitySystem::with_abilities(&boss.abilities())));
+    }
+    if !definition.loot.is_empty() {
+        commands.entity(enemy).insert(LootTable(definition.loot.clone()));
+    }
+    let health_bar = commands.spawn(HealthBarBundle::new(health_percent, assets.load("ui/health_bar.png"), Vec2::from(definition.health_bar_offset))).id();
+    commands.entity(enemy).push_children(&[health_bar]);
+    enemy
+}
@@ -0,0 +1,433 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::abilities::abilities::AbilitySystem;
+use crate::animation::directional_animator::{AnimationType, DirectionalAnimator};
+use crate::entity::health::Health;
+use crate::pathfinding::{AIPath, AITarget, Grid, GRID_TOLERANCE};
+use crate::player::Player;
+
+use super::perception::Perception;
+use super::tactics::{may_attack, AttackSlot, Coordinated};
+use super::territory::Territory;
+use super::{Enemy, EnemyState};
+
+pub struct StateMachinePlugin;
+
+impl Plugin for StateMachinePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StateMachine>()
+           .add_event::<StateTransitionEvent>()
+           // NOTE: Runs after every state's Update systems so signals raised this frame are acted on straight away
+           .add_systems(PostUpdate, update_state_machines);
+    }
+}
+
+/// What a transition waits for before its guards are checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect, Deserialize)]
+pub enum Trigger {
+    /// Checked every frame
+    #[default]
+    Always,
+    /// The state's timer ran out or its system reported it was done
+    Finished,
+    /// The state's system reported it was interrupted, e.g. a dash hitting a wall
+    Blocked
+}
+
+#[derive(Debug, Clone, Copy, Reflect, Deserialize)]
+pub enum Distance {
+    AttackRange,
+    FollowRange,
+    Fixed(f32)
+}
+
+/// A condition that has to hold for a transition to be taken
+#[derive(Debug, Clone, Copy, Reflect, Deserialize)]
+pub enum Guard {
+    PlayerWithin(Distance),
+    PlayerBeyond(Distance),
+    /// Health percentage in `[0, 1]`
+    HealthBelow(f32),
+    HealthAbove(f32),
+    /// Any ability in the enemy's `AbilitySystem` is off cooldown
+    AbilityReady,
+    /// Nothing solid between the enemy and the player
+    LineOfSight,
+    NoLineOfSight,
+    /// Reached the pathfinding destination
+    AtDestination,
+    NoPlayer,
+    MinTimeInState(f32),
+    /// The player is inside the vision cone and not behind a wall, see `Perception`
+    CanSeePlayer,
+    /// The player hasn't been seen for at least this many seconds
+    LostPlayer(f32),
+    /// Heard a spell cast or impact since last searching
+    HeardNoise,
+    /// Holds one of the `AttackCoordinator`'s tokens, always true for uncoordinated enemies
+    HasAttackSlot,
+    /// Further from home than the `Territory` leash allows, never true for enemies without one
+    OutsideTerritory,
+    /// The player is within the `Territory` leash, always true for enemies without one if there is a player
+    PlayerInTerritory
+}
+
+#[derive(Debug, Clone, Reflect, Deserialize)]
+pub struct Transition {
+    /// States this can be taken from, empty means any state
+    #[serde(default)]
+    pub from: Vec<EnemyState>,
+    pub to: EnemyState,
+    #[serde(default)]
+    pub on: Trigger,
+    #[serde(default)]
+    pub guards: Vec<Guard>
+}
+
+/// Common enter/exit behaviour that doesn't need its own system
+#[derive(Debug, Clone, Copy, Reflect, Deserialize)]
+pub enum StateAction {
+    PlayAnimation(AnimationType),
+    /// Turns pathfinding on or off, clearing any current path
+    Pathfind(bool),
+    StopMoving,
+    /// Raises `Finished` after this many seconds
+    Timer(f32),
+    RandomTimer(f32, f32)
+}
+
+#[derive(Debug, Clone, Reflect, Deserialize)]
+pub struct StateConfig {
+    pub state: EnemyState,
+    #[serde(default)]
+    pub on_enter: Vec<StateAction>,
+    #[serde(default)]
+    pub on_exit: Vec<StateAction>
+}
+
+/// Sent whenever an enemy changes state, for anything that needs to hook in from code
+#[derive(Event)]
+pub struct StateTransitionEvent {
+    pub entity: Entity,
+    pub from: EnemyState,
+    pub to: EnemyState
+}
+
+/// Table driven state machine, transitions are checked in order and the first one that passes is taken.
+/// Each state is also a marker component so its behaviour can be written as ordinary systems
+#[derive(Component, Reflect)]
+pub struct StateMachine {
+    pub state: EnemyState,
+    pub states: Vec<StateConfig>,
+    pub transitions: Vec<Transition>,
+    pub time_in_state: f32,
+    timer: Option<Timer>,
+    signal: Option<Trigger>,
+    entered: bool
+}
+
+impl StateMachine {
+    pub fn new(initial: EnemyState, states: Vec<StateConfig>, transitions: Vec<Transition>) -> Self {
+        StateMachine { state: initial, states, transitions, time_in_state: 0.0, timer: None, signal: None, entered: false }
+    }
+
+    /// Lets a state's system report it has finished or been interrupted
+    pub fn signal(&mut self, trigger: Trigger) {
+        self.signal = Some(trigger);
+    }
+
+    /// Raises `Finished` after `duration` seconds in the current state
+    pub fn start_timer(&mut self, duration: f32) {
+        self.timer = Some(Timer::from_seconds(duration, TimerMode::Once));
+    }
+
+    fn actions(&self, state: &EnemyState, exit: bool) -> Vec<StateAction> {
+        self.states.iter()
+            .find(|config| config.state == *state)
+            .map(|config| if exit { config.on_exit.clone() } else { config.on_enter.clone() })
+            .unwrap_or_default()
+    }
+
+    fn select(&self, trigger: Option<Trigger>, mut passes: impl FnMut(&Guard) -> bool) -> Option<&Transition> {
+        self.transitions.iter().find(|transition| {
+            let from_matches = if transition.from.is_empty() { transition.to != self.state } else { transition.from.contains(&self.state) };
+            let triggered = transition.on == Trigger::Always || Some(transition.on) == trigger;
+            from_matches && triggered && transition.guards.iter().all(&mut passes)
+        })
+    }
+}
+
+pub(super) struct GuardContext {
+    pub player_distance: Option<f32>,
+    pub attack_range: f32,
+    pub follow_range: f32,
+    pub health_percent: f32,
+    pub ability_ready: bool,
+    pub at_destination: bool,
+    pub time_in_state: f32,
+    pub can_see_player: bool,
+    pub time_since_seen: f32,
+    pub heard_noise: bool,
+    pub may_attack: bool,
+    pub home_distance: Option<f32>,
+    pub player_home_distance: Option<f32>,
+    pub leash_radius: f32
+}
+
+impl GuardContext {
+    /// Enemies without a `Perception` always know where the player is
+    pub fn perceive(&mut self, perception: Option<&Perception>) {
+        match perception {
+            Some(perception) => {
+                self.can_see_player = perception.can_see_player;
+                self.time_since_seen = perception.time_since_seen;
+                self.heard_noise = perception.heard_noise;
+            },
+            None => {
+                self.can_see_player = self.player_distance.is_some();
+                self.time_since_seen = if self.can_see_player { 0.0 } else { f32::INFINITY };
+                self.heard_noise = false;
+            }
+        }
+    }
+
+    /// Enemies without a `Territory` are never leashed
+    pub fn territory(&mut self, territory: Option<&Territory>, position: Vec2, player_pos: Option<Vec2>) {
+        self.home_distance = territory.map(|territory| territory.home.distance(position));
+        self.player_home_distance = territory.zip(player_pos).map(|(territory, player_pos)| territory.home.distance(player_pos));
+        self.leash_radius = territory.map_or(f32::INFINITY, |territory| territory.leash_radius);
+    }
+}
+
+impl Distance {
+    fn resolve(&self, context: &GuardContext) -> f32 {
+        match self {
+            Distance::AttackRange => context.attack_range,
+            Distance::FollowRange => context.follow_range,
+            Distance::Fixed(distance) => *distance,
+        }
+    }
+}
+
+impl Guard {
+    /// Line of sight needs a raycast so it is only worked out for guards that ask for it
+    pub(super) fn passes(&self, context: &GuardContext, line_of_sight: &mut impl FnMut() -> bool) -> bool {
+        match self {
+            Guard::PlayerWithin(distance) => context.player_distance.is_some_and(|player_distance| player_distance <= distance.resolve(context)),
+            Guard::PlayerBeyond(distance) => context.player_distance.is_some_and(|player_distance| player_distance > distance.resolve(context)),
+            Guard::HealthBelow(percent) => context.health_percent < *percent,
+            Guard::HealthAbove(percent) => context.health_percent > *percent,
+            Guard::AbilityReady => context.ability_ready,
+            Guard::LineOfSight => context.player_distance.is_some() && line_of_sight(),
+            Guard::NoLineOfSight => context.player_distance.is_some() && !line_of_sight(),
+            Guard::AtDestination => context.at_destination,
+            Guard::NoPlayer => context.player_distance.is_none(),
+            Guard::MinTimeInState(time) => context.time_in_state >= *time,
+            Guard::CanSeePlayer => context.can_see_player,
+            Guard::LostPlayer(time) => !context.can_see_player && context.time_since_seen >= *time,
+            Guard::HeardNoise => context.heard_noise,
+            Guard::HasAttackSlot => context.may_attack,
+            Guard::OutsideTerritory => context.home_distance.is_some_and(|distance| distance > context.leash_radius),
+            Guard::PlayerInTerritory => context.player_distance.is_some() && context.player_home_distance.map_or(true, |distance| distance <= context.leash_radius),
+        }
+    }
+}
+
+/// True if nothing solid is between `position` and the player
+pub(super) fn has_line_of_sight(rapier: &RapierContext, entity: Entity, position: Vec2, player: (Entity, Vec2)) -> bool {
+    let (player_entity, player_pos) = player;
+    // NOTE: Enemies are sensors so excluding sensors leaves walls and the player
+    let filter = QueryFilter::default().exclude_sensors().exclude_collider(entity);
+    let offset = player_pos - position;
+    match rapier.cast_ray(position, offset.normalize_or_zero(), offset.length(), true, filter) {
+        Some((hit, _)) => hit == player_entity,
+        None => true,
+    }
+}
+
+fn run_actions(
+    actions: &[StateAction],
+    entity: Entity,
+    commands: &mut Commands,
+    machine: &mut StateMachine,
+    ai: &mut AITarget,
+    animator: &mut DirectionalAnimator,
+    velocity: &mut Velocity
+) {
+    for action in actions {
+        match action {
+            StateAction::PlayAnimation(animation) => animator.update_animation(*animation),
+            StateAction::Pathfind(enabled) => {
+                ai.do_path_find = *enabled;
+                commands.entity(entity).remove::<AIPath>();
+            },
+            StateAction::StopMoving => {
+                ai.do_path_find = false;
+                velocity.linvel = Vec2::ZERO;
+                commands.entity(entity).remove::<AIPath>();
+            },
+            StateAction::Timer(duration) => machine.start_timer(*duration),
+            StateAction::RandomTimer(min, max) => machine.start_timer(rand::thread_rng().gen_range(*min..=max.max(*min))),
+        }
+    }
+}
+
+pub fn update_state_machines(
+    time: Res<Time>,
+    grid: Res<Grid>,
+    rapier: Res<RapierContext>,
+    mut commands: Commands,
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    mut machines: Query<(Entity, &mut StateMachine, &mut Enemy, &Transform, &mut AITarget, &Health, &mut DirectionalAnimator, &mut Velocity, Option<&AbilitySystem>, Option<&Perception>, Option<&AttackSlot>, Has<Coordinated>, Option<&Territory>), Without<Player>>,
+    mut ev_transition: EventWriter<StateTransitionEvent>
+) {
+    let player = player_query.get_single().ok().map(|(entity, transform)| (entity, transform.translation.truncate()));
+    for (entity, mut machine, mut enemy, transform, mut ai, health, mut animator, mut velocity, abilities, perception, slot, coordinated, territory) in machines.iter_mut() {
+        if !machine.entered {
+            let actions = machine.actions(&machine.state, false);
+            run_actions(&actions, entity, &mut commands, &mut machine, &mut ai, &mut animator, &mut velocity);
+            machine.state.clone().spawn(entity, &mut commands);
+            enemy.enemy_state = machine.state.clone();
+            machine.entered = true;
+            continue;
+        }
+        machine.time_in_state += time.delta_seconds();
+        let mut trigger = machine.signal.take();
+        if let Some(timer) = machine.timer.as_mut() {
+            timer.tick(Duration::from_secs_f32(time.delta_seconds()));
+            if timer.finished() && trigger.is_none() {
+                trigger = Some(Trigger::Finished);
+            }
+        }
+        let position = transform.translation.truncate();
+        let mut context = GuardContext {
+            player_distance: player.map(|(_, player_pos)| position.distance(player_pos)),
+            attack_range: ai.attack_range,
+            follow_range: ai.follow_range,
+            health_percent: health.get_percent(),
+            ability_ready: abilities.is_some_and(|abilities| abilities.abilities.iter().any(|ability| ability.can_use())),
+            at_destination: position.distance_squared(grid.grid_to_world(ai.destination)) <= GRID_TOLERANCE.powi(2),
+            time_in_state: machine.time_in_state,
+            can_see_player: false,
+            time_since_seen: 0.0,
+            heard_noise: false,
+            may_attack: may_attack(slot, coordinated),
+            home_distance: None,
+            player_home_distance: None,
+            leash_radius: f32::INFINITY
+        };
+        context.perceive(perception);
+        context.territory(territory, position, player.map(|(_, player_pos)| player_pos));
+        let mut line_of_sight_cache: Option<bool> = None;
+        let mut line_of_sight = || *line_of_sight_cache.get_or_insert_with(|| {
+            player.is_some_and(|player| has_line_of_sight(&rapier, entity, position, player))
+        });
+        let Some(to) = machine.select(trigger, |guard| guard.passes(&context, &mut line_of_sight)).map(|transition| transition.to.clone()) else { continue; };
+        let from = machine.state.clone();
+        let exit_actions = machine.actions(&from, true);
+        run_actions(&exit_actions, entity, &mut commands, &mut machine, &mut ai, &mut animator, &mut velocity);
+        from.remove(entity, &mut commands);
+        machine.state = to.clone();
+        machine.time_in_state = 0.0;
+        machine.timer = None;
+        let enter_actions = machine.actions(&to, false);
+        run_actions(&enter_actions, entity, &mut commands, &mut machine, &mut ai, &mut animator, &mut velocity);
+        to.clone().spawn(entity, &mut commands);
+        enemy.enemy_state = to.clone();
+        ev_transition.send(StateTransitionEvent { entity, from, to });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::territory::TerritoryDefinition;
+
+    fn context(player_distance: Option<f32>, health_percent: f32) -> GuardContext {
+        let mut context = GuardContext { player_distance, attack_range: 16.0, follow_range: 256.0, health_percent, ability_ready: false, at_destination: false, time_in_state: 0.0, can_see_player: false, time_since_seen: 0.0, heard_noise: false, may_attack: true, home_distance: None, player_home_distance: None, leash_radius: f32::INFINITY };
+        context.perceive(None);
+        context
+    }
+
+    fn orc() -> StateMachine {
+        StateMachine::new(EnemyState::Chase, Vec::new(), vec![
+            Transition { from: vec![EnemyState::Chase], to: EnemyState::Wander, on: Trigger::Always, guards: vec![Guard::PlayerBeyond(Distance::FollowRange)] },
+            Transition { from: vec![EnemyState::Chase], to: EnemyState::Attack, on: Trigger::Always, guards: vec![Guard::PlayerWithin(Distance::AttackRange)] },
+            Transition { from: vec![EnemyState::Attack], to: EnemyState::Chase, on: Trigger::Finished, guards: Vec::new() },
+            Transition { from: Vec::new(), to: EnemyState::Idle, on: Trigger::Always, guards: vec![Guard::NoPlayer] },
+        ])
+    }
+
+    fn select(machine: &StateMachine, trigger: Option<Trigger>, context: &GuardContext) -> Option<EnemyState> {
+        machine.select(trigger, |guard| guard.passes(context, &mut || true)).map(|transition| transition.to.clone())
+    }
+
+    #[test]
+    fn test_guards_pick_transition() {
+        let machine = orc();
+        assert_eq!(select(&machine, None, &context(Some(10.0), 1.0)), Some(EnemyState::Attack));
+        assert_eq!(select(&machine, None, &context(Some(300.0), 1.0)), Some(EnemyState::Wander));
+        assert_eq!(select(&machine, None, &context(Some(100.0), 1.0)), None);
+    }
+
+    #[test]
+    fn test_trigger_required() {
+        let mut machine = orc();
+        machine.state = EnemyState::Attack;
+        assert_eq!(select(&machine, None, &context(Some(10.0), 1.0)), None);
+        assert_eq!(select(&machine, Some(Trigger::Finished), &context(Some(10.0), 1.0)), Some(EnemyState::Chase));
+    }
+
+    #[test]
+    fn test_any_state_transition() {
+        let mut machine = orc();
+        machine.state = EnemyState::Attack;
+        assert_eq!(select(&machine, None, &context(None, 1.0)), Some(EnemyState::Idle));
+        // NOTE: Transitions from any state don't re-enter the state they lead to
+        machine.state = EnemyState::Idle;
+        assert_eq!(select(&machine, None, &context(None, 1.0)), None);
+    }
+
+    #[test]
+    fn test_health_guards() {
+        let guard = Guard::HealthBelow(0.5);
+        assert!(guard.passes(&context(None, 0.25), &mut || true));
+        assert!(!guard.passes(&context(None, 0.75), &mut || true));
+    }
+
+    #[test]
+    fn test_perception_guards() {
+        let mut perception = Perception::new(&Default::default(), 256.0);
+        perception.time_since_seen = 2.0;
+        perception.heard_noise = true;
+        let mut hidden = context(Some(100.0), 1.0);
+        hidden.perceive(Some(&perception));
+        assert!(!Guard::CanSeePlayer.passes(&hidden, &mut || true));
+        assert!(Guard::LostPlayer(1.0).passes(&hidden, &mut || true));
+        assert!(!Guard::LostPlayer(3.0).passes(&hidden, &mut || true));
+        assert!(Guard::HeardNoise.passes(&hidden, &mut || true));
+        // NOTE: Without perception the player is always known about, like before
+        assert!(Guard::CanSeePlayer.passes(&context(Some(100.0), 1.0), &mut || true));
+    }
+
+    #[test]
+    fn test_territory_guards() {
+        let territory = Territory::new(&TerritoryDefinition { radius: 100.0, leash_radius: 200.0, regenerate: false }, Vec2::ZERO);
+        let mut leashed = context(Some(100.0), 1.0);
+        leashed.territory(Some(&territory), Vec2::new(250.0, 0.0), Some(Vec2::new(350.0, 0.0)));
+        assert!(Guard::OutsideTerritory.passes(&leashed, &mut || true));
+        assert!(!Guard::PlayerInTerritory.passes(&leashed, &mut || true));
+        let mut home = context(Some(100.0), 1.0);
+        home.territory(Some(&territory), Vec2::new(50.0, 0.0), Some(Vec2::new(150.0, 0.0)));
+        assert!(!Guard::OutsideTerritory.passes(&home, &mut || true));
+        assert!(Guard::PlayerInTerritory.passes(&home, &mut || true));
+        // NOTE: Without a territory nothing is ever leashed
+        assert!(!Guard::OutsideTerritory.passes(&context(Some(100.0), 1.0), &mut || true));
+        assert!(Guard::PlayerInTerritory.passes(&context(Some(100.0), 1.0), &mut || true));
+    }
+}
@@ -0,0 +1,189 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::pathfinding::AITarget;
+use crate::player::Player;
+
+use super::orc::{Attack, Chase};
+
+/// How far inside its attack range an attacker stands in its slot
+const SLOT_RANGE: f32 = 0.75;
+
+pub struct TacticsPlugin;
+
+impl Plugin for TacticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AttackCoordinator>()
+           .register_type::<AttackCoordinator>()
+           .register_type::<AttackSlot>()
+           .register_type::<Coordinated>()
+           .add_systems(Update, coordinate_attackers);
+    }
+}
+
+/// Hands out a limited number of attack tokens to coordinated enemies chasing the player.
+/// Token holders spread out to slots around the player, everyone else circles at range
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct AttackCoordinator {
+    pub max_attackers: usize,
+    /// Evenly spaced positions around the player that attackers path to
+    pub slot_count: usize,
+    pub circle_radius: f32,
+    /// Radians per second waiting enemies move around the player
+    pub circle_speed: f32,
+    pub attackers: Vec<Entity>,
+    timer: Timer,
+}
+
+impl Default for AttackCoordinator {
+    fn default() -> Self {
+        AttackCoordinator {
+            max_attackers: 2,
+            slot_count: 6,
+            circle_radius: 96.0,
+            circle_speed: 0.5,
+            attackers: Vec::new(),
+            // NOTE: Destinations are only refreshed a few times a second so paths aren't rebuilt every frame
+            timer: Timer::from_seconds(0.25, TimerMode::Repeating)
+        }
+    }
+}
+
+/// Takes part in group tactics, set from `coordinated` in the archetype's AI definition
+#[derive(Component, Reflect)]
+pub struct Coordinated;
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum SlotRole {
+    Attacker(usize),
+    /// Angle around the player in radians
+    Waiting(f32),
+}
+
+/// Where the coordinator wants this enemy, `chase_update` paths here instead of straight at the player
+#[derive(Component, Reflect)]
+pub struct AttackSlot {
+    pub role: SlotRole,
+    pub position: Vec2,
+}
+
+/// Uncoordinated enemies can always attack, coordinated ones need a token
+pub fn may_attack(slot: Option<&AttackSlot>, coordinated: bool) -> bool {
+    match slot {
+        Some(slot) => matches!(slot.role, SlotRole::Attacker(_)),
+        None => !coordinated,
+    }
+}
+
+fn slot_angle(slot: usize, slot_count: usize) -> f32 {
+    TAU * slot as f32 / slot_count.max(1) as f32
+}
+
+/// Free slot closest to `angle`, so attackers don't cross over the player to reach theirs
+fn nearest_free_slot(angle: f32, slot_count: usize, taken: &[usize]) -> Option<usize> {
+    (0..slot_count)
+        .filter(|slot| !taken.contains(slot))
+        .min_by(|a, b| angle_difference(angle, slot_angle(*a, slot_count)).total_cmp(&angle_difference(angle, slot_angle(*b, slot_count))))
+}
+
+fn angle_difference(a: f32, b: f32) -> f32 {
+    let difference = (a - b).rem_euclid(TAU);
+    difference.min(TAU - difference)
+}
+
+fn coordinate_attackers(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut coordinator: ResMut<AttackCoordinator>,
+    player_query: Query<&Transform, With<Player>>,
+    enemies: Query<(Entity, &Transform, &AITarget, Option<&AttackSlot>, Has<Chase>, Has<Attack>), With<Coordinated>>
+) {
+    if !coordinator.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(player_transform) = player_query.get_single() else {
+        coordinator.attackers.clear();
+        for (entity, _, _, slot, _, _) in enemies.iter() {
+            if slot.is_some() {
+                commands.entity(entity).remove::<AttackSlot>();
+            }
+        }
+        return;
+    };
+    let player_pos = player_transform.translation.truncate();
+    let mut engaged = Vec::new();
+    for (entity, transform, ai, slot, chasing, attacking) in enemies.iter() {
+        if chasing || attacking {
+            engaged.push((entity, transform.translation.truncate(), ai.attack_range, slot.map(|slot| slot.role)));
+        } else if slot.is_some() {
+            commands.entity(entity).remove::<AttackSlot>();
+        }
+    }
+    coordinator.attackers.retain(|attacker| engaged.iter().any(|(entity, ..)| entity == attacker));
+    engaged.sort_by(|a, b| a.1.distance_squared(player_pos).total_cmp(&b.1.distance_squared(player_pos)));
+    for (entity, ..) in engaged.iter() {
+        if coordinator.attackers.len() >= coordinator.max_attackers {
+            break;
+        }
+        if !coordinator.attackers.contains(entity) {
+            coordinator.attackers.push(*entity);
+        }
+    }
+    let mut taken: Vec<usize> = engaged.iter()
+        .filter(|(entity, ..)| coordinator.attackers.contains(entity))
+        .filter_map(|(.., role)| match role { Some(SlotRole::Attacker(slot)) => Some(*slot), _ => None })
+        .collect();
+    let step = coordinator.circle_speed * coordinator.timer.duration().as_secs_f32();
+    for (entity, position, attack_range, role) in engaged {
+        let angle = Vec2::X.angle_between(position - player_pos);
+        let role = if coordinator.attackers.contains(&entity) {
+            match role {
+                Some(SlotRole::Attacker(slot)) => SlotRole::Attacker(slot),
+                _ => match nearest_free_slot(angle, coordinator.slot_count, &taken) {
+                    Some(slot) => {
+                        taken.push(slot);
+                        SlotRole::Attacker(slot)
+                    },
+                    None => SlotRole::Waiting(angle),
+                }
+            }
+        } else {
+            match role {
+                Some(SlotRole::Waiting(previous)) => SlotRole::Waiting(previous + step),
+                _ => SlotRole::Waiting(angle),
+            }
+        };
+        let (angle, distance) = match role {
+            SlotRole::Attacker(slot) => (slot_angle(slot, coordinator.slot_count), attack_range * SLOT_RANGE),
+            SlotRole::Waiting(angle) => (angle, coordinator.circle_radius),
+        };
+        let position = player_pos + Vec2::from_angle(angle) * distance;
+        commands.entity(entity).insert(AttackSlot { role, position });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_free_slot() {
+        assert_eq!(nearest_free_slot(0.1, 6, &[]), Some(0));
+        assert_eq!(nearest_free_slot(0.1, 6, &[0]), Some(1));
+        // NOTE: Just under a full turn is closest to slot 0, not the last slot
+        assert_eq!(nearest_free_slot(TAU - 0.1, 6, &[]), Some(0));
+        assert_eq!(nearest_free_slot(0.0, 2, &[0, 1]), None);
+    }
+
+    #[test]
+    fn test_may_attack() {
+        let attacker = AttackSlot { role: SlotRole::Attacker(0), position: Vec2::ZERO };
+        let waiting = AttackSlot { role: SlotRole::Waiting(0.0), position: Vec2::ZERO };
+        assert!(may_attack(Some(&attacker), true));
+        assert!(!may_attack(Some(&waiting), true));
+        assert!(!may_attack(None, true));
+        assert!(may_attack(None, false));
+    }
+}
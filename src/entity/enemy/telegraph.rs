@@ -0,0 +1,117 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::entity::{damage::{AttackerSnapshot, DamageType}, health::{DamageInstance, Health}};
+use crate::player::Player;
+
+/// Alpha the telegraph starts at, it fills up to `END_ALPHA` as the attack gets closer
+const START_ALPHA: f32 = 0.1;
+const END_ALPHA: f32 = 0.6;
+// NOTE: Sits just behind enemies (z -1) so it reads as being on the ground
+const TELEGRAPH_Z: f32 = -1.5;
+
+pub struct TelegraphPlugin;
+
+impl Plugin for TelegraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Telegraph>()
+           .add_systems(Update, (update_telegraphs, resolve_pending_strikes.after(update_telegraphs)));
+    }
+}
+
+/// Warning shape drawn on the ground before an enemy attack lands, despawns itself once `remaining` runs out
+#[derive(Component, Reflect)]
+pub struct Telegraph {
+    pub duration: f32,
+    pub remaining: f32
+}
+
+/// Damage dealt to the player if they are still inside the telegraph when it runs out
+#[derive(Component)]
+pub struct PendingStrike {
+    pub damage: f32,
+    pub knockback: f32,
+    pub attacker: AttackerSnapshot,
+    pub owner: Entity
+}
+
+/// A rectangle starting at `origin` and extending `length` along `direction`, for dashes and projectiles
+pub fn spawn_line_telegraph(commands: &mut Commands, origin: Vec2, direction: Vec2, length: f32, width: f32, duration: f32, colour: Color) -> Entity {
+    let angle = Vec2::X.angle_between(direction.normalize_or_zero());
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite { color: colour.with_a(START_ALPHA), custom_size: Some(Vec2::new(length, width)), anchor: Anchor::CenterLeft, ..default() },
+            transform: Transform::from_translation(origin.extend(TELEGRAPH_Z)).with_rotation(Quat::from_rotation_z(angle)),
+            ..default()
+        },
+        Telegraph { duration, remaining: duration },
+        Name::new("Telegraph")
+    )).id()
+}
+
+/// A square centred on `position`, for area attacks
+pub fn spawn_area_telegraph(commands: &mut Commands, position: Vec2, size: f32, duration: f32, colour: Color) -> Entity {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite { color: colour.with_a(START_ALPHA), custom_size: Some(Vec2::splat(size)), ..default() },
+            transform: Transform::from_translation(position.extend(TELEGRAPH_Z)),
+            ..default()
+        },
+        Telegraph { duration, remaining: duration },
+        Name::new("Telegraph")
+    )).id()
+}
+
+/// An area telegraph that hits the player with `strike` when it finishes
+pub fn spawn_area_strike(commands: &mut Commands, position: Vec2, size: f32, duration: f32, colour: Color, strike: PendingStrike) -> Entity {
+    let telegraph = spawn_area_telegraph(commands, position, size, duration, colour);
+    commands.entity(telegraph).insert(strike);
+    telegraph
+}
+
+fn update_telegraphs(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut telegraphs: Query<(Entity, &mut Telegraph, &mut Sprite, Option<&PendingStrike>)>
+) {
+    for (entity, mut telegraph, mut sprite, strike) in telegraphs.iter_mut() {
+        telegraph.remaining -= time.delta_seconds();
+        if telegraph.remaining <= 0.0 {
+            // NOTE: Strikes are despawned once they have been resolved
+            if strike.is_none() {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        }
+        let progress = 1.0 - (telegraph.remaining / telegraph.duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+        sprite.color.set_a(START_ALPHA + (END_ALPHA - START_ALPHA) * progress);
+    }
+}
+
+fn resolve_pending_strikes(
+    mut commands: Commands,
+    mut player_query: Query<(&mut Health, &Transform), With<Player>>,
+    strikes: Query<(Entity, &Telegraph, &PendingStrike, &Sprite, &GlobalTransform)>
+) {
+    let mut player = player_query.get_single_mut().ok();
+    for (entity, telegraph, strike, sprite, transform) in strikes.iter() {
+        if telegraph.remaining > 0.0 { continue; }
+        commands.entity(entity).despawn_recursive();
+        let Some((player_health, player_transform)) = player.as_mut() else { continue; };
+        let size = sprite.custom_size.unwrap_or(Vec2::ZERO);
+        // NOTE: Works in the telegraph's local space so rotated line telegraphs are handled the same as areas
+        let local = transform.affine().inverse().transform_point3(player_transform.translation).truncate();
+        let centre = match sprite.anchor {
+            Anchor::CenterLeft => Vec2::new(size.x * 0.5, 0.0),
+            _ => Vec2::ZERO,
+        };
+        let offset = local - centre;
+        if offset.x.abs() > size.x * 0.5 || offset.y.abs() > size.y * 0.5 { continue; }
+        let direction = (player_transform.translation - transform.translation()).truncate().normalize_or_zero();
+        player_health.push(
+            DamageInstance::new(strike.damage, DamageType::PHYSICAL, true)
+                .with_attacker(strike.attacker)
+                .with_source(strike.owner)
+                .with_knockback(direction * strike.knockback)
+        );
+    }
+}
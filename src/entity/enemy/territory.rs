@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::entity::health::Health;
+use crate::map::PatrolRoute;
+use crate::pathfinding::{AIPath, AITarget, Grid, GridPos};
+
+use super::orc::{pick_wander_destination, DESTINATION_SEARCH_DISTANCE};
+use super::waves::WaveMember;
+
+/// Fraction of max health restored per second while returning home
+const REGEN_RATE: f32 = 0.2;
+const REGEN_INTERVAL: f32 = 0.5;
+
+pub struct TerritoryPlugin;
+
+impl Plugin for TerritoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Territory>()
+           .register_type::<Patrol>()
+           .register_type::<Return>()
+           .add_systems(Update, (assign_patrols, return_enter, regenerate_while_returning));
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct TerritoryDefinition {
+    /// Wandering and patrols stay within this distance of home
+    pub radius: f32,
+    /// Chasing or searching further than this from home sends the enemy back
+    pub leash_radius: f32,
+    #[serde(default)]
+    pub regenerate: bool,
+}
+
+/// Where an enemy spawned and how far it will stray from there
+#[derive(Component, Reflect)]
+pub struct Territory {
+    pub home: Vec2,
+    pub radius: f32,
+    pub leash_radius: f32,
+    pub regenerate: bool,
+}
+
+impl Territory {
+    pub fn new(definition: &TerritoryDefinition, home: Vec2) -> Self {
+        Territory { home, radius: definition.radius, leash_radius: definition.leash_radius.max(definition.radius), regenerate: definition.regenerate }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        self.home.distance_squared(point) <= self.radius * self.radius
+    }
+
+    pub fn is_leashed(&self, point: Vec2) -> bool {
+        self.home.distance_squared(point) > self.leash_radius * self.leash_radius
+    }
+}
+
+/// The waypoints of a `PatrolRoute` inside the enemy's territory, walked in order while wandering
+#[derive(Component, Reflect)]
+pub struct Patrol {
+    pub waypoints: Vec<Vec2>,
+    pub index: usize,
+}
+
+impl Patrol {
+    /// The waypoint to head to next, looping back to the start at the end
+    pub fn advance(&mut self) -> Option<Vec2> {
+        let waypoint = *self.waypoints.get(self.index % self.waypoints.len().max(1))?;
+        self.index = (self.index + 1) % self.waypoints.len();
+        Some(waypoint)
+    }
+}
+
+/// Walking back home after being pulled out of its territory
+#[derive(Component, Reflect, Default)]
+pub struct Return {
+    regen_timer: f32,
+}
+
+/// Next patrol waypoint, a random point in the territory, or anywhere nearby for enemies without either
+pub fn wander_destination(grid: &Grid, position: Vec2, territory: Option<&Territory>, patrol: Option<&mut Patrol>) -> Option<GridPos> {
+    if let Some(waypoint) = patrol.and_then(|patrol| patrol.advance()) {
+        return grid.nearest_walkable(waypoint, DESTINATION_SEARCH_DISTANCE);
+    }
+    match territory {
+        Some(territory) => grid.random_reachable_point(territory.home, territory.radius, &mut rand::thread_rng()),
+        None => pick_wander_destination(grid, position)
+    }
+}
+
+/// Gives each new territory the closest route that has at least two waypoints inside it.
+/// Wave enemies are skipped since `release_wave_territories` takes their territory away in the same frame
+pub(super) fn assign_patrols(
+    mut commands: Commands,
+    territories: Query<(Entity, &Territory), (Added<Territory>, Without<WaveMember>)>,
+    routes: Query<&PatrolRoute>
+) {
+    for (entity, territory) in territories.iter() {
+        let closest = routes.iter()
+            .map(|route| route.waypoints.iter().copied().filter(|waypoint| territory.contains(*waypoint)).collect::<Vec<Vec2>>())
+            .filter(|waypoints| waypoints.len() >= 2)
+            .min_by(|a, b| {
+                let distance = |waypoints: &Vec<Vec2>| waypoints.iter().map(|waypoint| waypoint.distance_squared(territory.home)).fold(f32::INFINITY, f32::min);
+                distance(a).total_cmp(&distance(b))
+            });
+        let Some(waypoints) = closest else { continue; };
+        // NOTE: Starts from the closest waypoint so the first leg isn't a walk across the territory
+        let index = waypoints.iter().enumerate()
+            .min_by(|(_, a), (_, b)| a.distance_squared(territory.home).total_cmp(&b.distance_squared(territory.home)))
+            .map_or(0, |(index, _)| index);
+        commands.entity(entity).insert(Patrol { waypoints, index });
+    }
+}
+
+fn return_enter(
+    mut commands: Commands,
+    grid: Res<Grid>,
+    mut enemies: Query<(Entity, &Territory, &mut AITarget), Added<Return>>
+) {
+    for (entity, territory, mut ai) in enemies.iter_mut() {
+        let Some(destination) = grid.nearest_walkable(territory.home, DESTINATION_SEARCH_DISTANCE) else { continue; };
+        ai.destination = destination;
+        commands.entity(entity).remove::<AIPath>();
+    }
+}
+
+fn regenerate_while_returning(
+    time: Res<Time>,
+    mut enemies: Query<(&Territory, &mut Return, &mut Health)>
+) {
+    for (territory, mut returning, mut health) in enemies.iter_mut() {
+        if !territory.regenerate { continue; }
+        returning.regen_timer += time.delta_seconds();
+        if returning.regen_timer < REGEN_INTERVAL { continue; }
+        returning.regen_timer -= REGEN_INTERVAL;
+        // NOTE: Heals a full interval at once so the health bar and combat log aren't updated every frame
+        let amount = health.get_max() * REGEN_RATE * REGEN_INTERVAL;
+        health.heal(amount);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_territory_bounds() {
+        let territory = Territory::new(&TerritoryDefinition { radius: 100.0, leash_radius: 50.0, regenerate: false }, Vec2::new(10.0, 0.0));
+        // NOTE: The leash is never shorter than the territory itself
+        assert_eq!(territory.leash_radius, 100.0);
+        assert!(territory.contains(Vec2::new(100.0, 0.0)));
+        assert!(!territory.is_leashed(Vec2::new(100.0, 0.0)));
+        assert!(territory.is_leashed(Vec2::new(-100.0, 0.0)));
+    }
+
+    #[test]
+    fn test_patrol_loops() {
+        let mut patrol = Patrol { waypoints: vec![Vec2::ZERO, Vec2::X, Vec2::Y], index: 1 };
+        assert_eq!(patrol.advance(), Some(Vec2::X));
+        assert_eq!(patrol.advance(), Some(Vec2::Y));
+        assert_eq!(patrol.advance(), Some(Vec2::ZERO));
+        assert_eq!(Patrol { waypoints: Vec::new(), index: 0 }.advance(), None);
+    }
+}
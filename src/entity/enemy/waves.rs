@@ -0,0 +1,296 @@
+use bevy::prelude::*;
+
+use crate::entity::{health::Health, stats::{Stats, StatModifier, StatType}};
+
+use super::elite::{self, Elite};
+use super::spawner::{EnemySpawner, Summoning};
+use super::territory::{assign_patrols, Patrol, Territory};
+use super::{Enemy, EnemyType};
+
+/// Edges of the map, far enough from the centre that waves don't appear on top of the player
+const SPAWN_POINTS: [Vec2; 8] = [
+    Vec2::new(-400.0, 400.0), Vec2::new(0.0, 420.0), Vec2::new(400.0, 400.0), Vec2::new(420.0, 0.0),
+    Vec2::new(400.0, -400.0), Vec2::new(0.0, -420.0), Vec2::new(-400.0, -400.0), Vec2::new(-420.0, 0.0),
+];
+const MAX_ELITE_CHANCE: f32 = 0.5;
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WaveDirector>()
+           .register_type::<WaveDirector>()
+           .register_type::<WaveMember>()
+           .add_event::<WaveStartedEvent>()
+           .add_event::<WaveCompletedEvent>()
+           .add_systems(Update, (update_wave_director, scale_wave_enemies, release_wave_territories.before(assign_patrols)));
+    }
+}
+
+#[derive(Debug, Clone, Reflect)]
+pub struct WaveEntry {
+    pub enemy_type: EnemyType,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Reflect)]
+pub struct WaveDefinition {
+    pub enemies: Vec<WaveEntry>,
+    /// Seconds between each spawner's spawns
+    pub spawn_interval: f32,
+    pub elite_chance: f32,
+    /// The next wave starts after this long even if the current one isn't cleared
+    pub time_limit: Option<f32>,
+}
+
+impl WaveDefinition {
+    fn new(enemies: &[(EnemyType, usize)], spawn_interval: f32, elite_chance: f32, time_limit: Option<f32>) -> Self {
+        WaveDefinition {
+            enemies: enemies.iter().map(|(enemy_type, count)| WaveEntry { enemy_type: *enemy_type, count: *count }).collect(),
+            spawn_interval,
+            elite_chance,
+            time_limit
+        }
+    }
+}
+
+/// How quickly waves get harder, `difficulty` starts at 1
+#[derive(Debug, Clone, Reflect)]
+pub struct DifficultyScaling {
+    pub per_wave: f32,
+    pub per_minute: f32,
+    /// Extra enemies per wave past the last authored one, as a fraction of its counts
+    pub count_per_extra_wave: f32,
+    pub elite_chance_per_wave: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum WavePhase {
+    /// Counting down to the next wave
+    Intermission(f32),
+    /// `time_limit` is copied from the wave's definition when it starts
+    InProgress { started_at: f32, time_limit: Option<f32> },
+}
+
+/// Runs survival waves, each wave is a set of spawners tagged with `WaveMember`
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct WaveDirector {
+    pub enabled: bool,
+    pub waves: Vec<WaveDefinition>,
+    /// Number of the current wave, 0 before the first starts
+    pub wave: usize,
+    pub phase: WavePhase,
+    pub intermission: f32,
+    pub scaling: DifficultyScaling,
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        WaveDirector {
+            enabled: true,
+            waves: vec![
+                WaveDefinition::new(&[(EnemyType::Orc, 4)], 1.5, 0.0, Some(60.0)),
+                WaveDefinition::new(&[(EnemyType::Orc, 4), (EnemyType::Archer, 2)], 1.5, 0.0, Some(75.0)),
+                WaveDefinition::new(&[(EnemyType::Orc, 4), (EnemyType::Charger, 2), (EnemyType::Shaman, 1)], 1.25, 0.05, Some(90.0)),
+                WaveDefinition::new(&[(EnemyType::Orc, 4), (EnemyType::Archer, 2), (EnemyType::Caster, 2), (EnemyType::Shaman, 1)], 1.25, 0.1, Some(90.0)),
+                WaveDefinition::new(&[(EnemyType::Orc, 4), (EnemyType::Archer, 3), (EnemyType::Caster, 2), (EnemyType::Charger, 3)], 1.0, 0.15, Some(120.0)),
+            ],
+            wave: 0,
+            phase: WavePhase::Intermission(3.0),
+            intermission: 8.0,
+            scaling: DifficultyScaling { per_wave: 0.15, per_minute: 0.05, count_per_extra_wave: 0.25, elite_chance_per_wave: 0.02 },
+        }
+    }
+}
+
+impl WaveDirector {
+    pub fn difficulty(&self, elapsed: f32) -> f32 {
+        1.0 + self.scaling.per_wave * self.wave.saturating_sub(1) as f32 + self.scaling.per_minute * elapsed / 60.0
+    }
+
+    /// The authored definition for `wave`, past the end of the list the last one repeats with more enemies
+    pub fn definition(&self, wave: usize) -> Option<WaveDefinition> {
+        let last = self.waves.len().checked_sub(1)?;
+        let mut definition = self.waves[(wave.saturating_sub(1)).min(last)].clone();
+        let extra_waves = wave.saturating_sub(self.waves.len()) as f32;
+        for entry in definition.enemies.iter_mut() {
+            entry.count = (entry.count as f32 * (1.0 + extra_waves * self.scaling.count_per_extra_wave)).ceil() as usize;
+        }
+        definition.elite_chance = (definition.elite_chance + self.scaling.elite_chance_per_wave * wave.saturating_sub(1) as f32).min(MAX_ELITE_CHANCE);
+        Some(definition)
+    }
+}
+
+/// Tags a wave's spawners and the enemies they spawn, `bonus` is the extra health and attack from difficulty
+#[derive(Component, Reflect, Clone)]
+pub struct WaveMember {
+    pub wave: usize,
+    pub bonus: f32,
+}
+
+#[derive(Event)]
+pub struct WaveStartedEvent {
+    pub wave: usize,
+    pub enemy_count: usize,
+    pub difficulty: f32,
+}
+
+#[derive(Event)]
+pub struct WaveCompletedEvent {
+    pub wave: usize,
+    pub duration: f32,
+    /// False if the wave ran out of time instead of being cleared
+    pub cleared: bool,
+}
+
+fn update_wave_director(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut director: ResMut<WaveDirector>,
+    spawners: Query<&WaveMember, Or<(With<EnemySpawner>, With<Summoning>)>>,
+    enemies: Query<(&WaveMember, &Health), With<Enemy>>,
+    mut ev_started: EventWriter<WaveStartedEvent>,
+    mut ev_completed: EventWriter<WaveCompletedEvent>
+) {
+    if !director.enabled {
+        return;
+    }
+    let now = time.elapsed_seconds();
+    match director.phase {
+        WavePhase::Intermission(remaining) => {
+            let remaining = remaining - time.delta_seconds();
+            if remaining > 0.0 {
+                director.phase = WavePhase::Intermission(remaining);
+                return;
+            }
+            director.wave += 1;
+            let wave = director.wave;
+            let Some(definition) = director.definition(wave) else { return; };
+            let difficulty = director.difficulty(now);
+            let member = WaveMember { wave, bonus: difficulty - 1.0 };
+            let spawn_interval = definition.spawn_interval / difficulty.sqrt();
+            for (index, entry) in definition.enemies.iter().enumerate() {
+                if entry.count == 0 {
+                    continue;
+                }
+                // NOTE: Each entry gets its own pair of edges so different archetypes come from different directions
+                let spawn_points = vec![SPAWN_POINTS[(wave + index * 3) % SPAWN_POINTS.len()], SPAWN_POINTS[(wave + index * 3 + 4) % SPAWN_POINTS.len()]];
+                commands.spawn((
+                    EnemySpawner::new(entry.enemy_type, spawn_interval, entry.count, spawn_points).with_elite_chance(definition.elite_chance),
+                    member.clone(),
+                    Name::new(format!("Wave {} Spawner", wave))
+                ));
+            }
+            director.phase = WavePhase::InProgress { started_at: now, time_limit: definition.time_limit };
+            ev_started.send(WaveStartedEvent { wave, enemy_count: definition.enemies.iter().map(|entry| entry.count).sum(), difficulty });
+        },
+        WavePhase::InProgress { started_at, time_limit } => {
+            let duration = now - started_at;
+            // NOTE: Survivors of waves that timed out don't hold up the current one
+            let wave = director.wave;
+            let cleared = spawners.iter().all(|member| member.wave != wave)
+                && enemies.iter().all(|(member, health)| member.wave != wave || health.is_dead());
+            let timed_out = time_limit.is_some_and(|limit| duration >= limit);
+            if !cleared && !timed_out {
+                return;
+            }
+            ev_completed.send(WaveCompletedEvent { wave: director.wave, duration, cleared });
+            director.phase = WavePhase::Intermission(director.intermission);
+        }
+    }
+}
+
+/// Health and attack percentages from wave difficulty plus the elite bonus
+pub fn stat_bonuses(wave_bonus: f32, elite: bool) -> (f32, f32) {
+    let wave_bonus = wave_bonus.max(0.0);
+    if elite {
+        (wave_bonus + elite::HEALTH_BONUS, wave_bonus + elite::ATTACK_BONUS)
+    } else {
+        (wave_bonus, wave_bonus)
+    }
+}
+
+/// NOTE: Both bonuses come from the enemy itself and `add_modifier` replaces by source, so they are summed into one modifier
+pub(super) fn scale_wave_enemies(mut enemies: Query<(Entity, Option<&WaveMember>, Has<Elite>, &mut Stats), (Or<(Added<WaveMember>, Added<Elite>)>, With<Enemy>)>) {
+    for (entity, member, elite, mut stats) in enemies.iter_mut() {
+        let (health, attack) = stat_bonuses(member.map_or(0.0, |member| member.bonus), elite);
+        if health > 0.0 {
+            stats.add_modifier(StatType::Health, StatModifier::percent(health, entity));
+        }
+        if attack > 0.0 {
+            stats.add_modifier(StatType::Attack, StatModifier::percent(attack, entity));
+        }
+    }
+}
+
+/// Wave enemies spawn at the map edge and are meant to hunt the player, so they aren't leashed to where they appeared
+pub(super) fn release_wave_territories(mut commands: Commands, enemies: Query<Entity, (Added<WaveMember>, With<Territory>)>) {
+    for entity in enemies.iter() {
+        commands.entity(entity).remove::<(Territory, Patrol)>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::map::PatrolRoute;
+
+    use super::*;
+    use super::super::territory::TerritoryDefinition;
+
+    #[test]
+    fn test_elite_wave_enemy_keeps_both_bonuses() {
+        assert_eq!(stat_bonuses(0.5, false), (0.5, 0.5));
+        assert_eq!(stat_bonuses(0.0, true), (elite::HEALTH_BONUS, elite::ATTACK_BONUS));
+        let mut world = World::new();
+        let enemy = world.spawn((
+            Enemy::new(EnemyType::Orc),
+            Stats::new(100.0, 0.0, 0.0, 50.0, 10.0, 0.0),
+            WaveMember { wave: 3, bonus: 0.5 },
+            Elite { affixes: Vec::new() }
+        )).id();
+        world.run_system_once(scale_wave_enemies);
+        let stats = world.get::<Stats>(enemy).unwrap();
+        assert_eq!(*stats.get_stat(StatType::Health).unwrap(), 100.0 * (1.0 + 0.5 + elite::HEALTH_BONUS));
+        assert_eq!(*stats.get_stat(StatType::Attack).unwrap(), 10.0 * (1.0 + 0.5 + elite::ATTACK_BONUS));
+    }
+
+    #[test]
+    fn test_wave_enemies_have_no_territory() {
+        let mut world = World::new();
+        let definition = TerritoryDefinition { radius: 256.0, leash_radius: 512.0, regenerate: false };
+        world.spawn(PatrolRoute { waypoints: vec![Vec2::ZERO, Vec2::new(50.0, 0.0), SPAWN_POINTS[0], SPAWN_POINTS[0] + Vec2::new(50.0, 0.0)] });
+        let wave_enemy = world.spawn((Territory::new(&definition, SPAWN_POINTS[0]), WaveMember { wave: 1, bonus: 0.0 })).id();
+        let map_enemy = world.spawn(Territory::new(&definition, Vec2::ZERO)).id();
+        // NOTE: Both systems' commands are applied together at the end, so a patrol inserted after the removal would survive
+        let mut schedule = Schedule::default();
+        schedule.add_systems((release_wave_territories, assign_patrols).chain_ignore_deferred());
+        schedule.run(&mut world);
+        assert!(world.get::<Territory>(wave_enemy).is_none());
+        assert!(world.get::<Patrol>(wave_enemy).is_none());
+        assert!(world.get::<Territory>(map_enemy).is_some());
+        assert!(world.get::<Patrol>(map_enemy).is_some());
+    }
+
+    #[test]
+    fn test_waves_past_the_list_grow() {
+        let director = WaveDirector::default();
+        let last = director.waves.len();
+        let authored: usize = director.definition(last).unwrap().enemies.iter().map(|entry| entry.count).sum();
+        let extra: usize = director.definition(last + 2).unwrap().enemies.iter().map(|entry| entry.count).sum();
+        assert!(extra > authored);
+        assert!(director.definition(100).unwrap().elite_chance <= MAX_ELITE_CHANCE);
+    }
+
+    #[test]
+    fn test_difficulty_scales_with_wave_and_time() {
+        let mut director = WaveDirector::default();
+        director.wave = 1;
+        assert_eq!(director.difficulty(0.0), 1.0);
+        director.wave = 3;
+        assert!(director.difficulty(0.0) > 1.0);
+        assert!(director.difficulty(120.0) > director.difficulty(0.0));
+    }
+}
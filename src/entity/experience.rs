@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+use bevy_hanabi::{ParticleEffect, ParticleEffectBundle};
+use rand::seq::SliceRandom;
+
+use crate::abilities::abilities::AutoDestroy;
+use crate::entity::particles::{ParticleType, Particles};
+use crate::player::Player;
+use super::{
+    health::{EntityType, HealthDeathEvent},
+    stats::{Stats, StatType},
+};
+
+/// How many upgrades are offered to pick from on each level up
+pub const UPGRADE_CHOICES: usize = 3;
+
+/// Flat stat increases applied on every level up
+pub const LEVEL_GROWTH: [(StatType, f32); 5] = [
+    (StatType::Health, 10.0),
+    (StatType::Defence, 1.0),
+    (StatType::MagicDefence, 1.0),
+    (StatType::Attack, 2.0),
+    (StatType::Magic, 2.0),
+];
+
+pub const UPGRADE_POOL: [Upgrade; 6] = [
+    Upgrade { stat_type: StatType::Health, amount: 25.0 },
+    Upgrade { stat_type: StatType::Defence, amount: 5.0 },
+    Upgrade { stat_type: StatType::MagicDefence, amount: 5.0 },
+    Upgrade { stat_type: StatType::Attack, amount: 5.0 },
+    Upgrade { stat_type: StatType::Magic, amount: 5.0 },
+    Upgrade { stat_type: StatType::Speed, amount: 5.0 },
+];
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LevelCurve>()
+            .add_event::<LevelUpEvent>()
+            .add_event::<UpgradeChosenEvent>()
+            .register_type::<Experience>()
+            .register_type::<ExperienceReward>()
+            .register_type::<LevelCurve>()
+            .add_systems(Update, (
+                grant_experience,
+                level_up.after(grant_experience),
+                level_up_particles.after(level_up),
+                apply_upgrade
+            ));
+    }
+}
+
+/// XP required per level follows `base * level ^ exponent`
+#[derive(Resource, Reflect)]
+pub struct LevelCurve {
+    pub base: f32,
+    pub exponent: f32
+}
+
+impl LevelCurve {
+    pub fn xp_to_next(&self, level: u32) -> f32 {
+        self.base * (level.max(1) as f32).powf(self.exponent)
+    }
+}
+
+impl Default for LevelCurve {
+    fn default() -> Self {
+        LevelCurve { base: 100.0, exponent: 1.5 }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug)]
+pub struct Upgrade {
+    pub stat_type: StatType,
+    pub amount: f32
+}
+
+#[derive(Component, Reflect)]
+pub struct Experience {
+    pub level: u32,
+    pub current: f32,
+    pub pending_upgrades: u32,
+    pub upgrade_choices: Vec<Upgrade>
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Experience { level: 1, current: 0.0, pending_upgrades: 0, upgrade_choices: Vec::new() }
+    }
+}
+
+impl Experience {
+    pub fn add_xp(&mut self, amount: f32) {
+        self.current += amount.max(0.0);
+    }
+
+    pub fn get_percent(&self, curve: &LevelCurve) -> f32 {
+        f32::clamp(self.current / curve.xp_to_next(self.level), 0.0, 1.0)
+    }
+
+    fn roll_upgrades(&mut self) {
+        let mut rng = rand::thread_rng();
+        self.upgrade_choices = UPGRADE_POOL.choose_multiple(&mut rng, UPGRADE_CHOICES).cloned().collect();
+    }
+}
+
+/// XP granted to the player when the owning entity dies
+#[derive(Component, Reflect, Clone, Copy)]
+pub struct ExperienceReward {
+    pub amount: f32
+}
+
+#[derive(Event)]
+pub struct LevelUpEvent {
+    pub entity: Entity,
+    pub level: u32
+}
+
+#[derive(Event)]
+pub struct UpgradeChosenEvent {
+    pub choice: usize
+}
+
+fn grant_experience(
+    mut evr_death: EventReader<HealthDeathEvent>,
+    rewards: Query<&ExperienceReward>,
+    mut player_q: Query<&mut Experience, With<Player>>
+) {
+    let Ok(mut experience) = player_q.get_single_mut() else { return; };
+    for death_event in evr_death.read() {
+        if death_event.entity_type == EntityType::Player { continue; }
+        let Ok(reward) = rewards.get(death_event.entity) else { continue; };
+        experience.add_xp(reward.amount);
+    }
+}
+
+fn level_up(
+    curve: Res<LevelCurve>,
+    mut ev_level_up: EventWriter<LevelUpEvent>,
+    mut query: Query<(Entity, &mut Experience, &mut Stats), Changed<Experience>>
+) {
+    for (entity, mut experience, mut stats) in query.iter_mut() {
+        while experience.current >= curve.xp_to_next(experience.level) {
+            experience.current -= curve.xp_to_next(experience.level);
+            experience.level += 1;
+            experience.pending_upgrades += 1;
+            for (stat_type, amount) in LEVEL_GROWTH {
+                stats.add_stat(stat_type, amount);
+            }
+            info!("Levelled up to {}", experience.level);
+            ev_level_up.send(LevelUpEvent { entity, level: experience.level });
+        }
+        if experience.pending_upgrades > 0 && experience.upgrade_choices.is_empty() {
+            experience.roll_upgrades();
+        }
+    }
+}
+
+fn apply_upgrade(
+    mut evr_upgrade: EventReader<UpgradeChosenEvent>,
+    mut player_q: Query<(&mut Experience, &mut Stats), With<Player>>
+) {
+    let Ok((mut experience, mut stats)) = player_q.get_single_mut() else { return; };
+    for upgrade_event in evr_upgrade.read() {
+        let Some(upgrade) = experience.upgrade_choices.get(upgrade_event.choice).copied() else { continue; };
+        stats.add_stat(upgrade.stat_type, upgrade.amount);
+        experience.pending_upgrades = experience.pending_upgrades.saturating_sub(1);
+        if experience.pending_upgrades > 0 {
+            experience.roll_upgrades();
+        } else {
+            experience.upgrade_choices.clear();
+        }
+    }
+}
+
+fn level_up_particles(
+    mut commands: Commands,
+    mut evr_level_up: EventReader<LevelUpEvent>,
+    transforms: Query<&Transform>,
+    particles: Res<Particles>
+) {
+    for level_up_event in evr_level_up.read() {
+        let Ok(transform) = transforms.get(level_up_event.entity) else { continue; };
+        let Some(effect) = particles.get_particle(ParticleType::LevelUp) else { return; };
+        commands.spawn(ParticleEffectBundle {
+            effect: ParticleEffect::new(effect),
+            transform: Transform::from_translation(transform.translation.truncate().extend(11.0)),
+            ..Default::default()
+        }).insert(AutoDestroy::new(1.0));
+    }
+}
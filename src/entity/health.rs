@@ -1,21 +1,59 @@
 use bevy::prelude::*;
+use bevy::ecs::entity::Entities;
 use bevy_hanabi::{ParticleEffect, ParticleEffectBundle};
-use crate::abilities::abilities::AutoDestroy;
+use crate::abilities::abilities::{AbilityType, AutoDestroy};
 use crate::entity::particles::ParticleType;
 use crate::entity::particles::Particles;
 use crate::player::Player;
 use super::damage::*;
-use bevy::utils::hashbrown::HashMap;
+use super::stats::{OnStatChangeEvent, Stats, StatType};
+use rand::Rng;
+
+/// Seconds without taking damage before regeneration kicks in
+const OUT_OF_COMBAT_DELAY: f32 = 5.0;
+/// Regeneration is applied in chunks so it doesn't send a heal event every frame
+const REGEN_INTERVAL: f32 = 1.0;
 
 pub struct HealthPlugin;
 
+/// Healing the player gets when something else dies
+#[derive(Resource, Reflect)]
+pub struct KillHealConfig {
+    pub amount: f32,
+    /// Only heal when the player (or something it owns) got the kill
+    pub require_player_kill: bool
+}
+
+impl Default for KillHealConfig {
+    fn default() -> Self {
+        KillHealConfig { amount: 10.0, require_player_kill: false }
+    }
+}
+
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_event::<HealthDamageEvent>()
             .add_event::<HealthDeathEvent>()
+            .add_event::<HealthHealEvent>()
+            .add_event::<DamageReflectEvent>()
+            .init_resource::<KillHealConfig>()
             .register_type::<Health>()
-            .add_systems(Update, (health_update, death_update, on_damage));
+            .register_type::<KillHealConfig>()
+            .add_systems(Update, (
+                tick_dots.before(health_update),
+                tick_hots.before(health_update),
+                regenerate_health.before(health_update),
+                heal_on_kill,
+                health_update, 
+                death_update, 
+                on_damage, 
+                sync_health_with_stats, 
+                tick_invulnerability, 
+                tick_shields, 
+                cleanup_orphaned_shields, 
+                apply_reflected_damage.after(health_update)
+            ));
     }
 }
 
@@ -23,40 +61,397 @@ impl Plugin for HealthPlugin {
 pub struct Health {
     current_health: f32,
     max_health: f32,
-    magical_defence: i32,
-    physical_defence: i32,
+    magical_defence: f32,
+    physical_defence: f32,
+    damage_reduction: f32,
     dead: bool,
     is_invulnerable: bool,
+    invulnerability_duration: f32,
+    invulnerability_remaining: f32,
     entity_type: EntityType,
     incoming_damage: Vec<DamageInstance>,
+    shields: Vec<Shield>,
     #[reflect(ignore)]
-    dots: HashMap<u32, DOT>
+    pending_heals: Vec<(f32, Option<Entity>, Option<AbilityType>)>,
+    dots: Vec<DOT>,
+    hots: Vec<HOT>,
+    /// Last entity to deal damage, given kill credit on death
+    last_attacker: Option<Entity>,
+    regeneration: f32,
+    /// Time since damage was last taken
+    out_of_combat: f32,
+    regen_timer: f32
 }
 
 #[derive(Debug, Reflect, Clone, Copy)]
 pub struct DamageInstance {
     amount: f32, 
     damage_type: DamageType,
-    spawn_damage_particles: bool
+    spawn_damage_particles: bool,
+    is_dot: bool,
+    attacker: Option<AttackerSnapshot>,
+    source: Option<Entity>,
+    ability: Option<AbilityType>,
+    knockback: Vec2
 }
 
 impl DamageInstance {
     pub fn new(amount: f32, damage_type: DamageType, spawn_damage_particles: bool) -> Self {
-        DamageInstance { amount, damage_type, spawn_damage_particles }
+        DamageInstance { amount, damage_type, spawn_damage_particles, is_dot: false, attacker: None, source: None, ability: None, knockback: Vec2::ZERO }
+    }
+
+    pub fn dot(amount: f32, damage_type: DamageType) -> Self {
+        DamageInstance { amount, damage_type, spawn_damage_particles: false, is_dot: true, attacker: None, source: None, ability: None, knockback: Vec2::ZERO }
+    }
+
+    pub fn with_attacker(mut self, attacker: AttackerSnapshot) -> Self {
+        self.attacker = Some(attacker);
+        self
+    }
+
+    /// The entity that dealt the damage, reflected damage is sent back to it
+    pub fn with_source(mut self, source: Entity) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_ability(mut self, ability: AbilityType) -> Self {
+        self.ability = Some(ability);
+        self
+    }
+
+    /// `knockback` is the velocity applied to the target, so direction * ability weight
+    pub fn with_knockback(mut self, knockback: Vec2) -> Self {
+        self.knockback = knockback;
+        self
     }
 }
 
+/// Temporary health consumed before `current_health`, optionally only for one damage type
+#[derive(Clone, Copy, Reflect, Debug)]
+pub struct Shield {
+    pub amount: f32,
+    pub remaining: Option<f32>,
+    pub damage_type: Option<DamageType>,
+    /// Fraction of absorbed damage sent back to the attacker
+    pub reflect: f32,
+    pub source: Entity
+}
+
+impl Shield {
+    pub fn new(amount: f32, source: Entity) -> Self {
+        Shield { amount, remaining: None, damage_type: None, reflect: 0.0, source }
+    }
+
+    pub fn with_duration(mut self, duration: f32) -> Self {
+        self.remaining = Some(duration);
+        self
+    }
+
+    pub fn barrier(mut self, damage_type: DamageType) -> Self {
+        self.damage_type = Some(damage_type);
+        self
+    }
+
+    pub fn with_reflect(mut self, reflect: f32) -> Self {
+        self.reflect = reflect.clamp(0.0, 1.0);
+        self
+    }
+
+    fn blocks(&self, damage_type: DamageType) -> bool {
+        self.damage_type.map_or(true, |barrier_type| barrier_type == damage_type)
+    }
+}
+
+/// How reapplying a DoT or HoT from the same source behaves
+#[derive(Clone, Copy, Reflect, Debug, PartialEq, Eq)]
+pub enum DotStacking {
+    /// Reapplying resets the duration
+    Refresh,
+    /// Reapplying resets the duration and adds a stack, up to the limit
+    Intensity(u32),
+    /// Every application ticks on its own
+    Independent
+}
+
 #[derive(Clone, Reflect, Copy)]
 pub struct DOT {
     pub tick_damage: f32, 
+    pub tick_interval: f32,
     pub duration: f32, 
+    pub remaining: f32,
     pub damage_type: DamageType,
-    pub finished: bool
+    pub stacking: DotStacking,
+    pub stacks: u32,
+    /// The effect that applied this, used to decide whether a reapplication stacks
+    pub source: Entity,
+    /// Who gets credit for the damage
+    pub owner: Option<Entity>,
+    pub ability: Option<AbilityType>,
+    /// Stats of the caster when the DoT was applied
+    pub attacker: Option<AttackerSnapshot>,
+    pub finished: bool,
+    tick_timer: f32,
+    since_applied: f32
+}
+
+impl DOT {
+    pub fn new(tick_damage: f32, tick_interval: f32, duration: f32, damage_type: DamageType, stacking: DotStacking) -> Self {
+        DOT { 
+            tick_damage, 
+            tick_interval: tick_interval.max(0.01), 
+            duration, 
+            remaining: duration, 
+            damage_type, 
+            stacking, 
+            stacks: 1, 
+            source: Entity::PLACEHOLDER, 
+            owner: None, 
+            ability: None, 
+            attacker: None, 
+            finished: false, 
+            tick_timer: 0.0, 
+            since_applied: 0.0 
+        }
+    }
+
+    pub fn with_attacker(mut self, attacker: AttackerSnapshot) -> Self {
+        self.attacker = Some(attacker);
+        self
+    }
+
+    pub fn with_owner(mut self, owner: Entity) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub fn with_ability(mut self, ability: AbilityType) -> Self {
+        self.ability = Some(ability);
+        self
+    }
+
+    fn tick(&mut self, delta: f32) -> u32 {
+        tick_periodic(delta, self.tick_interval, &mut self.remaining, &mut self.tick_timer, &mut self.since_applied, &mut self.finished)
+    }
+}
+
+/// Heal over time, stacks the same way as `DOT`
+#[derive(Clone, Reflect, Copy)]
+pub struct HOT {
+    pub tick_heal: f32,
+    pub tick_interval: f32,
+    pub duration: f32,
+    pub remaining: f32,
+    pub stacking: DotStacking,
+    pub stacks: u32,
+    pub source: Entity,
+    pub owner: Option<Entity>,
+    pub ability: Option<AbilityType>,
+    pub finished: bool,
+    tick_timer: f32,
+    since_applied: f32
+}
+
+impl HOT {
+    pub fn new(tick_heal: f32, tick_interval: f32, duration: f32, stacking: DotStacking) -> Self {
+        HOT { 
+            tick_heal, 
+            tick_interval: tick_interval.max(0.01), 
+            duration, 
+            remaining: duration, 
+            stacking, 
+            stacks: 1, 
+            source: Entity::PLACEHOLDER, 
+            owner: None, 
+            ability: None, 
+            finished: false, 
+            tick_timer: 0.0, 
+            since_applied: 0.0 
+        }
+    }
+
+    pub fn with_owner(mut self, owner: Entity) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    pub fn with_ability(mut self, ability: AbilityType) -> Self {
+        self.ability = Some(ability);
+        self
+    }
+
+    fn tick(&mut self, delta: f32) -> u32 {
+        tick_periodic(delta, self.tick_interval, &mut self.remaining, &mut self.tick_timer, &mut self.since_applied, &mut self.finished)
+    }
+}
+
+/// Shared by DoTs and HoTs so reapplying either stacks the same way, see `add_periodic`
+trait PeriodicEffect {
+    fn source(&self) -> Entity;
+    fn set_source(&mut self, source: Entity);
+    fn stacking(&self) -> DotStacking;
+    fn stacks(&self) -> u32;
+    fn is_finished(&self) -> bool;
+    fn since_applied(&self) -> f32;
+    fn tick_interval(&self) -> f32;
+    /// Resets the remaining time to the duration of a reapplication
+    fn renew(&mut self, from: &Self);
+    fn add_stack(&mut self);
+    /// Hands credit for the effect to whoever reapplied it
+    fn take_credit(&mut self, from: &Self);
+}
+
+impl PeriodicEffect for DOT {
+    fn source(&self) -> Entity {
+        self.source
+    }
+
+    fn set_source(&mut self, source: Entity) {
+        self.source = source;
+    }
+
+    fn stacking(&self) -> DotStacking {
+        self.stacking
+    }
+
+    fn stacks(&self) -> u32 {
+        self.stacks
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn since_applied(&self) -> f32 {
+        self.since_applied
+    }
+
+    fn tick_interval(&self) -> f32 {
+        self.tick_interval
+    }
+
+    fn renew(&mut self, from: &Self) {
+        self.remaining = from.duration;
+    }
+
+    fn add_stack(&mut self) {
+        self.stacks += 1;
+        self.since_applied = 0.0;
+    }
+
+    fn take_credit(&mut self, from: &Self) {
+        self.attacker = from.attacker;
+        self.owner = from.owner;
+    }
+}
+
+impl PeriodicEffect for HOT {
+    fn source(&self) -> Entity {
+        self.source
+    }
+
+    fn set_source(&mut self, source: Entity) {
+        self.source = source;
+    }
+
+    fn stacking(&self) -> DotStacking {
+        self.stacking
+    }
+
+    fn stacks(&self) -> u32 {
+        self.stacks
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn since_applied(&self) -> f32 {
+        self.since_applied
+    }
+
+    fn tick_interval(&self) -> f32 {
+        self.tick_interval
+    }
+
+    fn renew(&mut self, from: &Self) {
+        self.remaining = from.duration;
+    }
+
+    fn add_stack(&mut self) {
+        self.stacks += 1;
+        self.since_applied = 0.0;
+    }
+
+    fn take_credit(&mut self, from: &Self) {
+        self.owner = from.owner;
+    }
+}
+
+/// A source can only add a stack or independent instance once per tick interval, 
+/// so overlapping area effects that reapply every frame don't instantly max out.
+/// Returns true if a new instance or stack was added rather than just refreshed
+fn add_periodic<T: PeriodicEffect>(effects: &mut Vec<T>, mut effect: T, source: Entity) -> bool {
+    effect.set_source(source);
+    let existing = effects.iter_mut()
+        .filter(|existing| existing.source() == source && !existing.is_finished())
+        .min_by(|a, b| a.since_applied().total_cmp(&b.since_applied()));
+    let Some(existing) = existing else {
+        effects.push(effect);
+        return true;
+    };
+    let can_stack = existing.since_applied() >= existing.tick_interval();
+    match effect.stacking() {
+        DotStacking::Refresh => {
+            existing.renew(&effect);
+            existing.take_credit(&effect);
+            false
+        },
+        DotStacking::Intensity(max_stacks) => {
+            existing.renew(&effect);
+            if can_stack && existing.stacks() < max_stacks {
+                existing.add_stack();
+                existing.take_credit(&effect);
+                return true;
+            }
+            false
+        },
+        DotStacking::Independent => {
+            if can_stack {
+                effects.push(effect);
+            }
+            can_stack
+        }
+    }
 }
 
-#[derive(Reflect, PartialEq, Eq)]
+/// Shared by DoTs and HoTs, returns how many ticks happened over `delta`
+fn tick_periodic(delta: f32, tick_interval: f32, remaining: &mut f32, tick_timer: &mut f32, since_applied: &mut f32, finished: &mut bool) -> u32 {
+    let delta = delta.min(*remaining);
+    *remaining -= delta;
+    *since_applied += delta;
+    *tick_timer += delta;
+    let mut ticks = 0;
+    while *tick_timer >= tick_interval {
+        *tick_timer -= tick_interval;
+        ticks += 1;
+    }
+    if *remaining <= 0.0 {
+        *finished = true;
+    }
+    ticks
+}
+
+#[derive(Reflect, PartialEq, Eq, Debug)]
 pub enum EntityType { Player, Enemy, Boss }
 
+impl EntityType {
+    /// Enemies and bosses are on the same side, the player is on its own
+    pub fn is_hostile_to(&self, other: &EntityType) -> bool {
+        (*self == EntityType::Player) != (*other == EntityType::Player)
+    }
+}
+
 impl Clone for EntityType {
     fn clone(&self) -> Self {
         match self {
@@ -67,23 +462,46 @@ impl Clone for EntityType {
     }
 }
 
-#[allow(dead_code)]
 #[derive(Event)]
 pub struct HealthDamageEvent {
-    entity: Entity, 
-    entity_type: EntityType,
-    pos: Vec2,
-    amount: f32
+    pub entity: Entity, 
+    pub entity_type: EntityType,
+    pub pos: Vec2,
+    pub amount: f32,
+    pub report: DamageReport,
+    pub is_dot: bool,
+    pub spawn_particles: bool,
+    pub knockback: Vec2,
+    pub source: Option<Entity>,
+    pub ability: Option<AbilityType>
+}
+
+#[derive(Event)]
+pub struct DamageReflectEvent {
+    pub target: Entity,
+    pub amount: f32,
+    pub damage_type: DamageType
+}
+
+#[derive(Event)]
+pub struct HealthHealEvent {
+    pub entity: Entity,
+    pub entity_type: EntityType,
+    pub pos: Vec2,
+    pub amount: f32,
+    pub source: Option<Entity>,
+    pub ability: Option<AbilityType>
 }
 
 fn on_damage(mut commands: Commands, mut evr: EventReader<HealthDamageEvent>, particles: Res<Particles>) {
     if evr.is_empty() { return; }
     let Some(effect) = particles.get_particle(ParticleType::Hit) else { return; };
     for event in evr.read() {
-        info!("{} was damaged!", event.entity.index());
+        if !event.spawn_particles { continue; }
+        info!("{:?} was damaged!", event.entity);
         commands.spawn(ParticleEffectBundle {
             effect: ParticleEffect::new(effect.clone()),
-            transform: Transform::from_scale(Vec3::splat(10.0)).with_translation(event.pos.extend(10.0)),
+            transform: Transform::from_scale(Vec3::splat(if event.is_dot { 5.0 } else { 10.0 })).with_translation(event.pos.extend(10.0)),
             ..Default::default()
         }).insert(AutoDestroy::new(0.25));
     }
@@ -93,20 +511,35 @@ fn on_damage(mut commands: Commands, mut evr: EventReader<HealthDamageEvent>, pa
 #[derive(Event)]
 pub struct HealthDeathEvent {
     pub entity: Entity, 
-    pub entity_type: EntityType
+    pub entity_type: EntityType,
+    /// Owner of the killing blow, may have despawned since
+    pub killer: Option<Entity>
 }
 
 pub fn death_update(
     mut commands: Commands,
-    mut player_q: Query<(&mut Health, &Transform), With<Player>>,
+    mut evr_death: EventReader<HealthDeathEvent>
+) {
+    for death_event in evr_death.read() {
+        if death_event.entity_type == EntityType::Player { continue; }
+        commands.entity(death_event.entity).despawn_recursive();
+    }
+}
+
+/// The only place kills heal the player, tune it through `KillHealConfig`
+pub fn heal_on_kill(
+    mut commands: Commands,
+    config: Res<KillHealConfig>,
+    mut player_q: Query<(Entity, &mut Health, &Transform), With<Player>>,
     mut evr_death: EventReader<HealthDeathEvent>,
     particles: Res<Particles>
 ) {
     if evr_death.is_empty() { return; }
-    let Ok((mut player_health, player_transform)) = player_q.get_single_mut() else { return; };
+    let Ok((player, mut player_health, player_transform)) = player_q.get_single_mut() else { return; };
     for death_event in evr_death.read() {
-        if death_event.entity_type == EntityType::Player { continue; }
-        player_health.heal(5.0);
+        if death_event.entity_type == EntityType::Player || config.amount <= 0.0 { continue; }
+        if config.require_player_kill && death_event.killer != Some(player) { continue; }
+        player_health.heal(config.amount);
         if let Some(effect_handle) = particles.get_particle(ParticleType::Heal) {
             commands.spawn(ParticleEffectBundle {
                 effect: ParticleEffect::new(effect_handle),
@@ -114,94 +547,317 @@ pub fn death_update(
                 ..Default::default()
             }).insert(AutoDestroy::new(0.5));
         }
-        commands.entity(death_event.entity).despawn_recursive();
     }
 }
 
 pub fn health_update(
-    time: Res<Time>, 
     mut ev_damage: EventWriter<HealthDamageEvent>, 
     mut ev_death: EventWriter<HealthDeathEvent>, 
+    mut ev_heal: EventWriter<HealthHealEvent>, 
+    mut ev_reflect: EventWriter<DamageReflectEvent>, 
     mut query: Query<(&mut Health, Entity, &Transform), Changed<Health>> // Adapt to use health UI later
 ) {
-    let mut damage_instances = Vec::<DamageInstance>::new();
+    let mut rng = rand::thread_rng();
     for (mut health, entity, transform) in query.iter_mut() {
         let en_type = health.entity_type.clone();
-        for damage_instance in &health.incoming_damage {
-            if damage_instance.spawn_damage_particles {
-                ev_damage.send(HealthDamageEvent { entity, entity_type: en_type.clone(), amount: damage_instance.amount, pos: transform.translation.truncate() });
+        let was_dead = health.dead;
+        for damage_instance in std::mem::take(&mut health.incoming_damage) {
+            let Some(report) = health.damage(&damage_instance, rng.gen()) else { continue; };
+            if let Some(source) = damage_instance.source {
+                health.last_attacker = Some(source);
             }
-            damage_instances.push(damage_instance.clone());
+            if let (Some(source), true) = (damage_instance.source, report.reflected > 0.0) {
+                ev_reflect.send(DamageReflectEvent { target: source, amount: report.reflected, damage_type: report.damage_type });
+            }
+            ev_damage.send(HealthDamageEvent { 
+                entity, 
+                entity_type: en_type.clone(), 
+                amount: report.final_amount, 
+                pos: transform.translation.truncate(), 
+                report,
+                is_dot: damage_instance.is_dot,
+                knockback: damage_instance.knockback,
+                spawn_particles: damage_instance.spawn_damage_particles,
+                source: damage_instance.source,
+                ability: damage_instance.ability
+            });
         }
-        for damage_instance in damage_instances.iter() {
-            health.damage(damage_instance.amount, damage_instance.damage_type);
+        for (amount, source, ability) in std::mem::take(&mut health.pending_heals) {
+            ev_heal.send(HealthHealEvent { entity, entity_type: en_type.clone(), pos: transform.translation.truncate(), amount, source, ability });
         }
-        health.incoming_damage.clear();
-        damage_instances.clear();
-        if health.dead {
-            ev_death.send( HealthDeathEvent { entity, entity_type: health.entity_type.clone() });
+        if health.dead && !was_dead {
+            ev_death.send( HealthDeathEvent { entity, entity_type: health.entity_type.clone(), killer: health.last_attacker });
         }
+    }
+}
 
-        if health.dots.len() == 0 {
-            return;
+pub fn tick_dots(time: Res<Time>, mut query: Query<&mut Health>) {
+    for mut health in query.iter_mut() {
+        if health.dots.is_empty() { continue; }
+        let mut ticks = Vec::new();
+        for dot in health.dots.iter_mut() {
+            for _ in 0..dot.tick(time.delta_seconds()) {
+                let mut instance = DamageInstance::dot(dot.tick_damage * dot.stacks as f32, dot.damage_type);
+                instance.spawn_damage_particles = true;
+                if let Some(attacker) = dot.attacker {
+                    instance = instance.with_attacker(attacker);
+                }
+                if let Some(owner) = dot.owner {
+                    instance = instance.with_source(owner);
+                }
+                if let Some(ability) = dot.ability {
+                    instance = instance.with_ability(ability);
+                }
+                ticks.push(instance);
+            }
         }
+        health.incoming_damage.extend(ticks);
+        health.dots.retain(|dot| !dot.finished);
+    }
+}
 
-        let mut finished: Vec<u32> = Vec::new();
-
-        for (entity, dot) in health.dots.iter_mut() {
-            dot.duration = (dot.duration - time.delta_seconds()).max(0.0);
-            damage_instances.push(DamageInstance::new(dot.tick_damage * time.delta_seconds(), dot.damage_type, false));
-            if dot.duration == 0.0 {
-                finished.push(*entity);
+pub fn tick_hots(time: Res<Time>, mut query: Query<&mut Health>) {
+    for mut health in query.iter_mut() {
+        if health.hots.is_empty() { continue; }
+        let mut heals = Vec::new();
+        for hot in health.hots.iter_mut() {
+            for _ in 0..hot.tick(time.delta_seconds()) {
+                heals.push((hot.tick_heal * hot.stacks as f32, hot.owner, hot.ability));
             }
         }
-        
-        for damage_instance in damage_instances.iter() {
-            health.incoming_damage.push(*damage_instance);
+        for (amount, owner, ability) in heals {
+            health.heal_from(amount, owner, ability);
         }
+        health.hots.retain(|hot| !hot.finished);
+    }
+}
+
+pub fn regenerate_health(time: Res<Time>, mut query: Query<&mut Health>) {
+    for mut health in query.iter_mut() {
+        // NOTE: The timers move every frame, only an actual heal should mark the health as changed
+        let regen = health.bypass_change_detection();
+        if regen.regeneration <= 0.0 || regen.dead { continue; }
+        regen.out_of_combat += time.delta_seconds();
+        if regen.out_of_combat < OUT_OF_COMBAT_DELAY || regen.current_health >= regen.max_health {
+            regen.regen_timer = 0.0;
+            continue;
+        }
+        regen.regen_timer += time.delta_seconds();
+        if regen.regen_timer < REGEN_INTERVAL { continue; }
+        regen.regen_timer -= REGEN_INTERVAL;
+        let amount = regen.regeneration * REGEN_INTERVAL;
+        regen.heal(amount);
+        health.set_changed();
+    }
+}
+
+pub fn tick_shields(time: Res<Time>, mut query: Query<&mut Health>) {
+    for mut health in query.iter_mut() {
+        if !health.shields.iter().any(|shield| shield.remaining.is_some()) { continue; }
+        for shield in health.shields.iter_mut() {
+            if let Some(remaining) = shield.remaining.as_mut() {
+                *remaining = (*remaining - time.delta_seconds()).max(0.0);
+            }
+        }
+        health.shields.retain(|shield| shield.remaining != Some(0.0));
+    }
+}
+
+/// Untimed shields only last as long as whatever granted them
+pub fn cleanup_orphaned_shields(entities: &Entities, mut query: Query<&mut Health>) {
+    for mut health in query.iter_mut() {
+        if health.shields.iter().all(|shield| shield.remaining.is_some() || entities.contains(shield.source)) { continue; }
+        health.shields.retain(|shield| shield.remaining.is_some() || entities.contains(shield.source));
+    }
+}
 
-        for id in finished {
-            health.dots.remove(&id);
+/// Reflected damage carries no source so two reflecting entities can't bounce it forever
+pub fn apply_reflected_damage(mut evr_reflect: EventReader<DamageReflectEvent>, mut query: Query<&mut Health>) {
+    for reflect_event in evr_reflect.read() {
+        let Ok(mut health) = query.get_mut(reflect_event.target) else { continue; };
+        health.push(DamageInstance::new(reflect_event.amount, reflect_event.damage_type, true));
+    }
+}
+
+pub fn tick_invulnerability(time: Res<Time>, mut query: Query<&mut Health>) {
+    for mut health in query.iter_mut() {
+        if !health.is_invulnerable { continue; }
+        health.invulnerability_remaining = (health.invulnerability_remaining - time.delta_seconds()).max(0.0);
+        if health.invulnerability_remaining == 0.0 {
+            health.is_invulnerable = false;
         }
     }
 }
 
+/// Keeps max health and defences in line with the owning entity's final stat values
+pub fn sync_health_with_stats(
+    mut evr_stat_change: EventReader<OnStatChangeEvent>,
+    mut query: Query<(&Stats, &mut Health)>
+) {
+    for stat_change in evr_stat_change.read() {
+        if !matches!(stat_change.stat_type, StatType::Health | StatType::Defence | StatType::MagicDefence | StatType::DamageReduction | StatType::Regeneration) { continue; }
+        let Ok((stats, mut health)) = query.get_mut(stat_change.entity) else { continue; };
+        health.sync_with_stats(stats);
+    }
+}
+
 impl Health {
-    pub fn new(health: f32, physical_defence: i32, magical_defence: i32, entity_type: EntityType) -> Health {
-        Self { current_health: health, max_health: health, magical_defence, physical_defence, dead: false, is_invulnerable: false, incoming_damage: Vec::new(), entity_type, dots: HashMap::new() }
+    pub fn from_stats(stats: &Stats, entity_type: EntityType) -> Health {
+        let max_health = *stats.get_stat(StatType::Health).unwrap_or(&100.0);
+        let mut health = Self { 
+            current_health: max_health, 
+            max_health, 
+            magical_defence: 0.0, 
+            physical_defence: 0.0, 
+            damage_reduction: 0.0, 
+            dead: false, 
+            is_invulnerable: false, 
+            invulnerability_duration: 0.0,
+            invulnerability_remaining: 0.0,
+            incoming_damage: Vec::new(), 
+            shields: Vec::new(), 
+            pending_heals: Vec::new(), 
+            entity_type, 
+            dots: Vec::new(),
+            hots: Vec::new(),
+            last_attacker: None,
+            regeneration: 0.0,
+            out_of_combat: 0.0,
+            regen_timer: 0.0
+        };
+        health.sync_with_stats(stats);
+        health
     }
 
+    /// Direct hits make the entity invulnerable for `duration` seconds, DoT ticks ignore this
+    pub fn with_invulnerability(mut self, duration: f32) -> Self {
+        self.invulnerability_duration = duration.max(0.0);
+        self
+    }
+
+    /// Current health is rescaled so the health percentage is kept when max health changes
+    pub fn sync_with_stats(&mut self, stats: &Stats) {
+        let max_health = stats.get_stat(StatType::Health).copied().unwrap_or(self.max_health).max(1.0);
+        if max_health != self.max_health {
+            self.current_health = self.current_health / self.max_health * max_health;
+            self.max_health = max_health;
+        }
+        self.physical_defence = stats.get_stat(StatType::Defence).copied().unwrap_or(self.physical_defence);
+        self.magical_defence = stats.get_stat(StatType::MagicDefence).copied().unwrap_or(self.magical_defence);
+        self.damage_reduction = stats.get_stat(StatType::DamageReduction).copied().unwrap_or(self.damage_reduction);
+        self.regeneration = stats.get_stat(StatType::Regeneration).copied().unwrap_or(self.regeneration);
+    }
+
+    #[allow(dead_code)]
     pub fn push_damage(&mut self, amount: f32, damage_type: DamageType) {
         self.incoming_damage.push(DamageInstance::new(amount, damage_type, true));
     }
 
-    fn damage(&mut self, mut amount: f32, damage_type: DamageType) {
-        if !self.dead && !self.is_invulnerable {
-            amount *= self.defence_multiplier(damage_type);
-            self.current_health = f32::max(0.0, self.current_health - amount);
-            if self.current_health == 0.0 {
-                self.dead = true;
-            }
+    /// Attacker stats on the instance scale the damage, see `damage::calculate_damage`
+    pub fn push(&mut self, damage_instance: DamageInstance) {
+        self.incoming_damage.push(damage_instance);
+    }
+
+    fn damage(&mut self, damage_instance: &DamageInstance, crit_roll: f32) -> Option<DamageReport> {
+        if self.dead || (self.is_invulnerable && !damage_instance.is_dot) {
+            return None;
+        }
+        self.out_of_combat = 0.0;
+        if !damage_instance.is_dot && self.invulnerability_duration > 0.0 {
+            self.is_invulnerable = true;
+            self.invulnerability_remaining = self.invulnerability_duration;
         }
+        let mut report = calculate_damage(
+            damage_instance.amount, 
+            damage_instance.damage_type, 
+            damage_instance.attacker.as_ref(), 
+            &self.defender_snapshot(), 
+            crit_roll
+        );
+        self.absorb(&mut report);
+        self.current_health = f32::max(0.0, self.current_health - report.final_amount);
+        if self.current_health == 0.0 {
+            self.dead = true;
+        }
+        Some(report)
     }
 
-    pub fn heal(&mut self, mut amount: f32) {
-        amount = amount.max(0.0);
-        self.current_health = self.max_health.min(self.current_health + amount);
+    /// Type specific barriers are used up before general shields
+    fn absorb(&mut self, report: &mut DamageReport) {
+        let damage_type = report.damage_type;
+        self.shields.sort_by_key(|shield| shield.damage_type.is_none());
+        for shield in self.shields.iter_mut().filter(|shield| shield.blocks(damage_type)) {
+            if report.final_amount <= 0.0 { break; }
+            let absorbed = shield.amount.min(report.final_amount);
+            shield.amount -= absorbed;
+            report.final_amount -= absorbed;
+            report.absorbed += absorbed;
+            report.reflected += absorbed * shield.reflect;
+        }
+        self.shields.retain(|shield| shield.amount > 0.0);
     }
 
-    pub fn add_dot(&mut self, damage_per_second: f32, duration: f32, damage_type: DamageType, entity_id: u32) {
-        self.dots.insert(entity_id, DOT { tick_damage: damage_per_second, duration, damage_type, finished: false });
+    /// Replaces any existing shield from the same source
+    pub fn add_shield(&mut self, shield: Shield) {
+        self.shields.retain(|existing| existing.source != shield.source);
+        self.shields.push(shield);
     }
 
-    fn defence_multiplier(&mut self, damage_type: DamageType) -> f32 {
-        match damage_type {
-            DamageType::PHYSICAL => multiplier_from_defence(self.physical_defence),
-            DamageType::MAGICAL => multiplier_from_defence(self.magical_defence),
-            _ => 1.0
+    pub fn get_shield(&self) -> f32 {
+        self.shields.iter().map(|shield| shield.amount).sum()
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        self.heal_from(amount, None, None);
+    }
+
+    /// Same as `heal` but keeps who healed and with what for the combat log
+    pub fn heal_from(&mut self, mut amount: f32, source: Option<Entity>, ability: Option<AbilityType>) {
+        if self.dead { return; }
+        amount = amount.max(0.0).min(self.max_health - self.current_health);
+        if amount <= 0.0 { return; }
+        self.current_health += amount;
+        self.pending_heals.push((amount, source, ability));
+    }
+
+    /// Stacks with an existing DoT from the same source, see `add_periodic`.
+    /// Returns true if a new instance or stack was added rather than just refreshed
+    pub fn add_dot(&mut self, dot: DOT, source: Entity) -> bool {
+        add_periodic(&mut self.dots, dot, source)
+    }
+
+    /// Same stacking rules as `add_dot`
+    pub fn add_hot(&mut self, hot: HOT, source: Entity) -> bool {
+        if self.dead { return false; }
+        add_periodic(&mut self.hots, hot, source)
+    }
+
+    pub fn entity_type(&self) -> &EntityType {
+        &self.entity_type
+    }
+
+    pub fn defender_snapshot(&self) -> DefenderSnapshot {
+        DefenderSnapshot { 
+            physical_defence: self.physical_defence, 
+            magical_defence: self.magical_defence, 
+            damage_reduction: self.damage_reduction 
         }
     }
 
+    /// Blocks direct hits for `duration` seconds, keeping any longer invulnerability already running
+    pub fn make_invulnerable(&mut self, duration: f32) {
+        self.is_invulnerable = true;
+        self.invulnerability_remaining = self.invulnerability_remaining.max(duration);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.dead
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.is_invulnerable
+    }
+
     pub fn get_percent(&self) -> f32 {
         f32::clamp(self.current_health / self.max_health, 0.0, 1.0)
     }
@@ -215,3 +871,74 @@ impl Health {
         self.current_health
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health() -> Health {
+        Health::from_stats(&Stats::new(100.0, 0.0, 0.0, 50.0, 0.0, 0.0), EntityType::Enemy)
+    }
+
+    fn dot(stacking: DotStacking) -> DOT {
+        DOT::new(5.0, 1.0, 4.0, DamageType::MAGICAL, stacking)
+    }
+
+    #[test]
+    fn test_dot_refresh() {
+        let mut health = health();
+        let source = Entity::from_raw(1);
+        assert!(health.add_dot(dot(DotStacking::Refresh), source));
+        health.dots[0].tick(2.0);
+        assert!(!health.add_dot(dot(DotStacking::Refresh), source));
+        assert_eq!(health.dots.len(), 1);
+        assert_eq!(health.dots[0].stacks, 1);
+        assert_eq!(health.dots[0].remaining, 4.0);
+    }
+
+    #[test]
+    fn test_dot_intensity_stacks_once_per_interval_up_to_max() {
+        let mut health = health();
+        let source = Entity::from_raw(1);
+        assert!(health.add_dot(dot(DotStacking::Intensity(2)), source));
+        // NOTE: Reapplied in the same tick interval, so only refreshed
+        assert!(!health.add_dot(dot(DotStacking::Intensity(2)), source));
+        assert_eq!(health.dots[0].stacks, 1);
+        health.dots[0].tick(1.0);
+        assert!(health.add_dot(dot(DotStacking::Intensity(2)), source));
+        assert_eq!(health.dots[0].stacks, 2);
+        health.dots[0].tick(1.0);
+        assert!(!health.add_dot(dot(DotStacking::Intensity(2)), source));
+        assert_eq!(health.dots[0].stacks, 2);
+        assert_eq!(health.dots[0].remaining, 4.0);
+        assert_eq!(health.dots.len(), 1);
+    }
+
+    #[test]
+    fn test_dot_independent() {
+        let mut health = health();
+        let (source, other) = (Entity::from_raw(1), Entity::from_raw(2));
+        assert!(health.add_dot(dot(DotStacking::Independent), source));
+        assert!(!health.add_dot(dot(DotStacking::Independent), source));
+        assert_eq!(health.dots.len(), 1);
+        // NOTE: The interval is per source, a different source applies straight away
+        assert!(health.add_dot(dot(DotStacking::Independent), other));
+        health.dots[0].tick(1.0);
+        assert!(health.add_dot(dot(DotStacking::Independent), source));
+        assert_eq!(health.dots.len(), 3);
+        assert!(health.dots.iter().all(|dot| dot.stacks == 1));
+    }
+
+    #[test]
+    fn test_shield_absorbs_and_reflects() {
+        let mut health = health();
+        let attacker = Entity::from_raw(1);
+        health.add_shield(Shield::new(30.0, Entity::from_raw(2)).with_reflect(0.5));
+        let report = health.damage(&DamageInstance::new(50.0, DamageType::BYPASS, false).with_source(attacker), 1.0).unwrap();
+        assert_eq!(report.absorbed, 30.0);
+        assert_eq!(report.reflected, 15.0);
+        assert_eq!(report.final_amount, 20.0);
+        assert_eq!(health.get_current(), 80.0);
+        assert_eq!(health.get_shield(), 0.0);
+    }
+}
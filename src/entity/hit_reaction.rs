@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::combat_log::{StatusAppliedEvent, StatusKind};
+use super::health::{Health, HealthDamageEvent};
+
+/// How long the flash colour is shown after a hit
+const FLASH_DURATION: f32 = 0.1;
+/// Blinks per second while invulnerable
+const BLINK_RATE: f32 = 12.0;
+const BLINK_ALPHA: f32 = 0.35;
+
+pub struct HitReactionPlugin;
+
+impl Plugin for HitReactionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<HitReaction>()
+            .register_type::<Stunned>()
+            .add_systems(Update, (apply_hit_reactions, update_stuns, flash_hit_sprites.after(apply_hit_reactions)));
+    }
+}
+
+#[derive(Component, Reflect, Clone)]
+pub struct HitReaction {
+    pub stun_duration: f32,
+    pub knockback_multiplier: f32,
+    pub flash_colour: Color,
+    pub base_colour: Color,
+    pub flash_remaining: f32
+}
+
+impl HitReaction {
+    pub fn new(stun_duration: f32, knockback_multiplier: f32) -> Self {
+        HitReaction { stun_duration, knockback_multiplier, flash_colour: Color::rgb(1.0, 0.3, 0.3), base_colour: Color::WHITE, flash_remaining: 0.0 }
+    }
+}
+
+/// Movement systems leave velocity alone while this is present so knockback can play out
+#[derive(Component, Reflect)]
+pub struct Stunned {
+    pub remaining: f32
+}
+
+fn apply_hit_reactions(
+    mut commands: Commands,
+    mut evr_damage: EventReader<HealthDamageEvent>,
+    mut targets: Query<(&mut HitReaction, Option<&mut Velocity>, Option<&mut Stunned>)>,
+    mut ev_status: EventWriter<StatusAppliedEvent>
+) {
+    for damage_event in evr_damage.read() {
+        if damage_event.is_dot { continue; }
+        let Ok((mut hit_reaction, velocity, stunned)) = targets.get_mut(damage_event.entity) else { continue; };
+        hit_reaction.flash_remaining = FLASH_DURATION;
+        if let Some(mut velocity) = velocity {
+            if damage_event.knockback.length_squared() > 0.0 {
+                velocity.linvel = damage_event.knockback * hit_reaction.knockback_multiplier;
+            }
+        }
+        if hit_reaction.stun_duration <= 0.0 { continue; }
+        match stunned {
+            Some(mut stunned) => stunned.remaining = stunned.remaining.max(hit_reaction.stun_duration),
+            None => {
+                commands.entity(damage_event.entity).insert(Stunned { remaining: hit_reaction.stun_duration });
+                ev_status.send(StatusAppliedEvent { 
+                    target: damage_event.entity, 
+                    source: damage_event.source, 
+                    ability: damage_event.ability, 
+                    status: StatusKind::Stun, 
+                    duration: hit_reaction.stun_duration 
+                });
+            }
+        }
+    }
+}
+
+fn update_stuns(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut stunned: Query<(Entity, &mut Stunned)>
+) {
+    for (entity, mut stun) in stunned.iter_mut() {
+        stun.remaining -= time.delta_seconds();
+        if stun.remaining <= 0.0 {
+            commands.entity(entity).remove::<Stunned>();
+        }
+    }
+}
+
+fn flash_hit_sprites(
+    time: Res<Time>,
+    mut query: Query<(&mut HitReaction, &Health, &mut Sprite)>
+) {
+    for (mut hit_reaction, health, mut sprite) in query.iter_mut() {
+        let colour = if hit_reaction.flash_remaining > 0.0 {
+            hit_reaction.flash_remaining -= time.delta_seconds();
+            hit_reaction.flash_colour
+        } else if health.is_invulnerable() && (time.elapsed_seconds() * BLINK_RATE) as i32 % 2 == 0 {
+            hit_reaction.base_colour.with_a(BLINK_ALPHA)
+        } else {
+            hit_reaction.base_colour
+        };
+        if sprite.color != colour {
+            sprite.color = colour;
+        }
+    }
+}
@@ -6,14 +6,21 @@ pub mod enemy;
 pub mod damage;
 pub mod player;
 pub mod health;
+pub mod experience;
+pub mod hit_reaction;
+pub mod combat_log;
 
 pub struct EntityPlugin;
 
 impl Plugin for EntityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, stats::update_stats)
+        app.add_event::<stats::OnStatChangeEvent>()
+            .add_systems(Update, (stats::update_stats, stats::cleanup_orphaned_modifiers))
             .add_plugins(enemy::EnemyPlugin)
             .add_plugins(player::PlayerPlugin)
-            .add_plugins(health::HealthPlugin);
+            .add_plugins(health::HealthPlugin)
+            .add_plugins(experience::ExperiencePlugin)
+            .add_plugins(hit_reaction::HitReactionPlugin)
+            .add_plugins(combat_log::CombatLogPlugin);
     }
 }
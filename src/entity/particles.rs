@@ -4,7 +4,8 @@ use bevy_hanabi::prelude::*;
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum ParticleType {
     Hit,
-    Heal
+    Heal,
+    LevelUp
 }
 
 pub struct ParticlePlugin;
@@ -40,6 +41,7 @@ fn init_particles(
 ) {
     particle_effects.effects.insert(ParticleType::Hit, hit_effect(&mut effects));
     particle_effects.effects.insert(ParticleType::Heal, heal_effect(&mut effects));
+    particle_effects.effects.insert(ParticleType::LevelUp, level_up_effect(&mut effects));
 }
 
 pub fn hit_effect(particle_effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<EffectAsset> {
@@ -127,3 +129,46 @@ pub fn heal_effect(particle_effects: &mut ResMut<Assets<EffectAsset>>) -> Handle
         })
     )
 }
+
+pub fn level_up_effect(particle_effects: &mut ResMut<Assets<EffectAsset>>) -> Handle<EffectAsset> {
+    let colour_grad = Gradient::linear(Vec4::new(1.0, 0.85, 0.2, 1.0), Vec4::new(1.0, 1.0, 0.6, 0.0));
+    let size_grad = Gradient::linear(Vec2::ONE * 6.0, Vec2::ONE);
+    let writer = ExprWriter::new();
+    let age = writer.lit(0.0).expr();
+    let init_age = SetAttributeModifier::new(Attribute::AGE, age);
+    let lifetime = writer.lit(0.8).uniform(writer.lit(1.2)).expr();
+    let init_lifetime = SetAttributeModifier::new(Attribute::LIFETIME, lifetime);
+    let init_pos = SetPositionCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        radius: writer.lit(10.0).uniform(writer.lit(20.0)).expr(),
+        dimension: ShapeDimension::Surface
+    };
+
+    let init_vel = SetVelocityCircleModifier {
+        center: writer.lit(Vec3::ZERO).expr(),
+        axis: writer.lit(Vec3::Z).expr(),
+        speed: writer.lit(20.0).expr()
+    };
+
+    let module = writer.finish();
+
+    particle_effects.add(
+        EffectAsset::new(
+            300, 
+            Spawner::rate(60.0.into()), 
+            module
+        ).with_name("Level Up Effect")
+        .init(init_lifetime)
+        .init(init_age)
+        .init(init_vel)
+        .init(init_pos)
+        .render(ColorOverLifetimeModifier {
+            gradient: colour_grad
+        })
+        .render(SizeOverLifetimeModifier {
+            gradient: size_grad,
+            screen_space_size: false
+        })
+    )
+}
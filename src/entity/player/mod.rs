@@ -6,7 +6,9 @@ pub struct Player;
 
 use crate::animation::{*, directional_animator::*};
 use super::{
+    experience::Experience,
     health::{EntityType, Health},
+    hit_reaction::{HitReaction, Stunned},
     stats::{Stats, StatType},
 };
 
@@ -15,9 +17,12 @@ use bevy::utils::hashbrown::HashMap;
 use bevy_rapier2d::prelude::*;
 use crate::abilities::abilities::AbilitySystem;
 
+/// Health per second once out of combat
+const PLAYER_REGENERATION: f32 = 2.0;
+
 pub fn player_move_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&mut Velocity, &Stats), With<Player>>,
+    mut query: Query<(&mut Velocity, &Stats), (With<Player>, Without<Stunned>)>,
 ) {
     let input: Vec2 = Vec2::new(
         (keyboard_input.pressed(KeyCode::KeyD) as i32 - keyboard_input.pressed(KeyCode::KeyA) as i32)
@@ -25,7 +30,7 @@ pub fn player_move_input(
         (keyboard_input.pressed(KeyCode::KeyW) as i32 - keyboard_input.pressed(KeyCode::KeyS) as i32)
             as f32,
     );
-    let (mut velocity, stats) = query.single_mut();
+    let Ok((mut velocity, stats)) = query.get_single_mut() else { return; };
     // NOTE: Rapier already applies deltaTime multiplication
     velocity.linvel = *stats.get_stat(StatType::Speed).unwrap_or(&50.0) * input; 
 }
@@ -38,10 +43,14 @@ pub fn spawn_player(
     let texture_handle: Handle<Image> = assets.load("player/player.png");
     let layout = TextureAtlasLayout::from_grid(Vec2::new(48.0, 64.0), 3, 4, None, None);
     let layout_handle = atlases.add(layout);
+    let mut stats = Stats::default();
+    stats.add_stat(StatType::Regeneration, PLAYER_REGENERATION);
+    let health = Health::from_stats(&stats, EntityType::Player).with_invulnerability(0.5);
+    let health_percent = health.get_percent();
     let player = commands.spawn((
         Player,
         Name::new("Player"),
-        Health::new(100.0, 10, 10, EntityType::Player),
+        health,
         DirectionalAnimator {
             animation_indices: HashMap::from([
                 (
@@ -84,10 +93,13 @@ pub fn spawn_player(
             angvel: 0.0,
         },
         Collider::capsule_y(8.0, 16.0),
-        Stats::default(),
+        Damping { linear_damping: 8.0, angular_damping: 0.0 },
+        HitReaction::new(0.1, 1.0),
+        stats,
         AbilitySystem::default(),
+        Experience::default(),
     )).id();
-    let health_bar = commands.spawn(HealthBarBundle::new(100.0, assets.load("ui/health_bar.png"), Vec2::new(0.0, 24.0))).id();
+    let health_bar = commands.spawn(HealthBarBundle::new(health_percent, assets.load("ui/health_bar.png"), Vec2::new(0.0, 24.0))).id();
     commands.get_entity(player).unwrap().insert_children(0, &[health_bar]);
 }
 
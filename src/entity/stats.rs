@@ -1,129 +1,323 @@
 use bevy::prelude::*;
+use bevy::ecs::entity::Entities;
 use bevy::utils::hashbrown::HashMap;
 use bevy_inspector_egui::InspectorOptions;
+use serde::Deserialize;
 
-#[derive(Clone, Copy, Hash, Eq, PartialEq, Reflect, Debug)]
-pub enum StatType { Health, Defence, MagicDefence, Attack, Magic, Speed }
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Reflect, Debug, Deserialize)]
+pub enum StatType { 
+    Health, 
+    Defence, 
+    MagicDefence, 
+    Attack, 
+    Magic, 
+    Speed, 
+    CritChance, 
+    CritDamage, 
+    ArmourPenetration, 
+    MagicPenetration, 
+    DamageReduction, 
+    /// Health per second while out of combat
+    Regeneration 
+}
 
-#[derive(Component, Clone, Reflect, InspectorOptions)]
-pub struct Stats {
-    pub stats: [Stat; 6],
-    #[reflect(ignore)]
-    pub stat_effects: HashMap<u32, StatChangeDuration>,
+/// Order modifiers are applied in: `(base + flat) * (1 + sum(percent)) * product(multiplicative)`
+#[derive(Clone, Copy, PartialEq, Eq, Reflect, Debug)]
+pub enum ModifierKind { Flat, PercentAdd, Multiplicative }
+
+#[derive(Clone, Copy, Reflect, Debug)]
+pub struct StatModifier {
+    pub kind: ModifierKind,
+    pub amount: f32,
+    pub source: Entity,
+    pub duration: Option<f32>
+}
+
+impl StatModifier {
+    pub fn flat(amount: f32, source: Entity) -> Self {
+        StatModifier { kind: ModifierKind::Flat, amount, source, duration: None }
+    }
+
+    /// `amount` is a fraction, so `0.1` is +10%
+    pub fn percent(amount: f32, source: Entity) -> Self {
+        StatModifier { kind: ModifierKind::PercentAdd, amount, source, duration: None }
+    }
+
+    pub fn multiplicative(amount: f32, source: Entity) -> Self {
+        StatModifier { kind: ModifierKind::Multiplicative, amount, source, duration: None }
+    }
+
+    pub fn with_duration(mut self, duration: f32) -> Self {
+        self.duration = Some(duration);
+        self
+    }
 }
 
-#[derive(Clone, Copy, Reflect)]
+#[derive(Clone, Reflect)]
 pub struct Stat {
     pub stat_type: StatType,
-    pub stat_value: f32
+    pub base_value: f32,
+    value: f32,
+    pub modifiers: Vec<StatModifier>
 }
+
 impl Stat {
-    fn new(stat_type: StatType, stat_value: f32) -> Self {
-        Stat { stat_type, stat_value }
+    fn new(stat_type: StatType, base_value: f32) -> Self {
+        Stat { stat_type, base_value, value: base_value, modifiers: Vec::new() }
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Returns the previous value if the final value changed
+    fn recalculate(&mut self) -> Option<f32> {
+        let mut flat = 0.0;
+        let mut percent = 0.0;
+        let mut multiplier = 1.0;
+        for modifier in self.modifiers.iter() {
+            match modifier.kind {
+                ModifierKind::Flat => flat += modifier.amount,
+                ModifierKind::PercentAdd => percent += modifier.amount,
+                ModifierKind::Multiplicative => multiplier *= modifier.amount,
+            }
+        }
+        // NOTE: Stats never go negative so large slows bottom out at 0 rather than being dropped
+        let value = ((self.base_value + flat) * (1.0 + percent) * multiplier).max(0.0);
+        if value == self.value {
+            return None;
+        }
+        let previous = self.value;
+        self.value = value;
+        Some(previous)
     }
 }
 
-#[derive(Clone, Copy)]
-pub struct StatChangeDuration {
-    pub stat_type: StatType,
-    pub amount: f32,
-    pub duration: f32
+#[derive(Component, Clone, Reflect, InspectorOptions)]
+pub struct Stats {
+    pub stats: HashMap<StatType, Stat>,
+    #[reflect(ignore)]
+    pending_changes: HashMap<StatType, f32>
 }
 
 impl Stats {
     pub fn add_stat(&mut self, stat_type: StatType, amount: f32) {
-        let Some(stat) = self.stats.iter_mut().find(|iter_stat| iter_stat.stat_type == stat_type) else { return; };
-        stat.stat_value += amount;
+        let Some(stat) = self.stats.get_mut(&stat_type) else { return; };
+        stat.base_value += amount;
+        self.recalculate(stat_type);
     }
 
     pub fn get_stat(&self, stat_type: StatType) -> Option<&f32> {
-        let stat = self.stats.iter().find(|stat| stat.stat_type == stat_type);
-        if stat.is_some() {
-            Some(&stat.unwrap().stat_value)
-        } else {
-            None
+        self.stats.get(&stat_type).map(|stat| &stat.value)
+    }
+
+    #[allow(dead_code)]
+    pub fn get_base(&self, stat_type: StatType) -> Option<f32> {
+        self.stats.get(&stat_type).map(|stat| stat.base_value)
+    }
+
+    pub fn has_modifier(&self, stat_type: StatType, source: Entity) -> bool {
+        self.stats.get(&stat_type).is_some_and(|stat| stat.modifiers.iter().any(|modifier| modifier.source == source))
+    }
+
+    /// Adds a modifier, replacing any modifier on the same stat from the same source
+    pub fn add_modifier(&mut self, stat_type: StatType, modifier: StatModifier) {
+        let Some(stat) = self.stats.get_mut(&stat_type) else { return; };
+        match stat.modifiers.iter_mut().find(|existing| existing.source == modifier.source) {
+            Some(existing) => *existing = modifier,
+            None => {
+                info!("Added {:?} modifier for {:?} from {:?}", modifier.kind, stat_type, modifier.source);
+                stat.modifiers.push(modifier);
+            }
         }
+        self.recalculate(stat_type);
     }
 
-    pub fn get_stat_mut(&mut self, stat_type: StatType) -> Option<&mut f32> {
-        let stat = self.stats.iter_mut().find(|stat| stat.stat_type == stat_type);
-        if stat.is_some() {
-            Some(&mut stat.unwrap().stat_value)
-        } else {
-            None
+    #[allow(dead_code)]
+    pub fn remove_modifiers(&mut self, source: Entity) {
+        self.retain_modifiers(|modifier| modifier.source != source);
+    }
+
+    /// Removes untimed modifiers whose source fails `is_alive`, timed ones are left to run out
+    pub fn remove_orphaned_modifiers(&mut self, is_alive: impl Fn(Entity) -> bool) {
+        self.retain_modifiers(|modifier| modifier.duration.is_some() || is_alive(modifier.source));
+    }
+
+    fn retain_modifiers(&mut self, keep: impl Fn(&StatModifier) -> bool) {
+        let mut changed = Vec::new();
+        for stat in self.stats.values_mut() {
+            let count = stat.modifiers.len();
+            stat.modifiers.retain(|modifier| keep(modifier));
+            if stat.modifiers.len() != count {
+                changed.push(stat.stat_type);
+            }
+        }
+        for stat_type in changed {
+            self.recalculate(stat_type);
+        }
+    }
+
+    /// Ticks timed modifiers, removing any that have run out
+    pub fn tick(&mut self, delta: f32) {
+        let mut changed = Vec::new();
+        for stat in self.stats.values_mut() {
+            let count = stat.modifiers.len();
+            for modifier in stat.modifiers.iter_mut() {
+                if let Some(duration) = modifier.duration.as_mut() {
+                    *duration = (*duration - delta).max(0.0);
+                }
+            }
+            stat.modifiers.retain(|modifier| modifier.duration != Some(0.0));
+            if stat.modifiers.len() != count {
+                changed.push(stat.stat_type);
+            }
+        }
+        for stat_type in changed {
+            self.recalculate(stat_type);
         }
     }
 
-    pub fn add_duration_change(&mut self, stat_type: StatType, amount: f32, duration: f32, id: u32, can_change_sign: bool) {
-        if self.stat_effects.contains_key(&id) {
-            return;
+    pub fn has_timed_modifiers(&self) -> bool {
+        self.stats.values().any(|stat| stat.modifiers.iter().any(|modifier| modifier.duration.is_some()))
+    }
+
+    pub fn has_pending_changes(&self) -> bool {
+        !self.pending_changes.is_empty()
+    }
+
+    /// Drains `(stat_type, previous, current)` for every stat whose final value changed
+    pub fn drain_changes(&mut self) -> Vec<(StatType, f32, f32)> {
+        let changes: Vec<(StatType, f32)> = self.pending_changes.drain().collect();
+        changes.into_iter()
+            .filter_map(|(stat_type, previous)| {
+                let current = *self.get_stat(stat_type)?;
+                if current == previous { None } else { Some((stat_type, previous, current)) }
+            })
+            .collect()
+    }
+
+    fn recalculate(&mut self, stat_type: StatType) {
+        let Some(stat) = self.stats.get_mut(&stat_type) else { return; };
+        if let Some(previous) = stat.recalculate() {
+            self.pending_changes.entry(stat_type).or_insert(previous);
         }
-        
-        info!("Added stat change for {:?} from {} for duration {}", stat_type, id, duration);
-        let amount = if !can_change_sign {
-            let Some(current_value) = self.get_stat(stat_type) else { return; };
-            if (current_value + amount).signum() == current_value.signum() { amount } else { 0.0 }
-        } else {
-            amount
-        };
-        self.add_stat(stat_type, amount);
-        self.stat_effects.insert(id, StatChangeDuration { stat_type, amount, duration });
-        assert!(self.stat_effects.len() > 0);
-        info!("Stat effect count for not {} is {}", id, self.stat_effects.len());
     }
 
     pub fn new(health: f32, defence: f32, mag_def: f32, speed: f32, attack: f32, magic: f32) -> Self {
         Stats {
-            stats: [
-                Stat::new(StatType::Health, health),
-                Stat::new(StatType::Defence, defence),
-                Stat::new(StatType::MagicDefence, mag_def),
-                Stat::new(StatType::Speed, speed),
-                Stat::new(StatType::Attack, attack),
-                Stat::new(StatType::Magic, magic),
-            ],
-            stat_effects: HashMap::new(),
+            stats: HashMap::from([
+                (StatType::Health, Stat::new(StatType::Health, health)),
+                (StatType::Defence, Stat::new(StatType::Defence, defence)),
+                (StatType::MagicDefence, Stat::new(StatType::MagicDefence, mag_def)),
+                (StatType::Speed, Stat::new(StatType::Speed, speed)),
+                (StatType::Attack, Stat::new(StatType::Attack, attack)),
+                (StatType::Magic, Stat::new(StatType::Magic, magic)),
+                (StatType::CritChance, Stat::new(StatType::CritChance, 0.05)),
+                (StatType::CritDamage, Stat::new(StatType::CritDamage, 1.5)),
+                (StatType::ArmourPenetration, Stat::new(StatType::ArmourPenetration, 0.0)),
+                (StatType::MagicPenetration, Stat::new(StatType::MagicPenetration, 0.0)),
+                (StatType::DamageReduction, Stat::new(StatType::DamageReduction, 0.0)),
+                (StatType::Regeneration, Stat::new(StatType::Regeneration, 0.0)),
+            ]),
+            pending_changes: HashMap::new(),
         }
     }
 }
 
-pub fn update_stats(time: Res<Time>, mut stats: Query<&mut Stats>) {
-    for mut stat in stats.iter_mut() {
-        if stat.stat_effects.keys().len() == 0 {
+pub fn update_stats(
+    time: Res<Time>,
+    mut stats: Query<(Entity, &mut Stats)>,
+    mut ev_stat_change: EventWriter<OnStatChangeEvent>
+) {
+    for (entity, mut stat) in stats.iter_mut() {
+        if !stat.has_timed_modifiers() && !stat.has_pending_changes() {
             continue;
         }
-        let mut finished: Vec<u32> = Vec::new();
-        for (id, stat_effect) in stat.stat_effects.iter_mut() {
-            stat_effect.duration = (stat_effect.duration - time.delta_seconds()).max(0.0);
-            info!("Updated stat duration to: {}", stat_effect.duration);
-            if stat_effect.duration == 0.0 {
-                info!("Stat effect {:?} is finished", stat_effect.stat_type);
-                finished.push(*id);
-            }
+        stat.tick(time.delta_seconds());
+        for (stat_type, previous, value) in stat.drain_changes() {
+            ev_stat_change.send(OnStatChangeEvent { entity, stat_type, previous, value });
         }
-        for id in finished {
-            let value = *stat.stat_effects.get(&id).unwrap();
-            stat.add_stat(value.stat_type, -value.amount);
-            stat.stat_effects.remove(&id);
+    }
+}
+
+pub fn cleanup_orphaned_modifiers(entities: &Entities, mut stats: Query<&mut Stats>) {
+    for mut stat in stats.iter_mut() {
+        let has_orphans = stat.stats.values()
+            .any(|s| s.modifiers.iter().any(|modifier| modifier.duration.is_none() && !entities.contains(modifier.source)));
+        if has_orphans {
+            stat.remove_orphaned_modifiers(|source| entities.contains(source));
         }
     }
 }
 
 impl Default for Stats {
     fn default() -> Self {
-        Stats { stats: [
-            Stat::new(StatType::Health, 100.0),
-            Stat::new(StatType::Defence, 10.0),
-            Stat::new(StatType::MagicDefence, 10.0),
-            Stat::new(StatType::Speed, 50.0),
-            Stat::new(StatType::Attack, 25.0),
-            Stat::new(StatType::Magic, 20.0)
-        ], stat_effects: HashMap::new() }
+        Stats::new(100.0, 10.0, 10.0, 50.0, 25.0, 20.0)
     }
 }
 
 #[derive(Event)]
 pub struct OnStatChangeEvent {
+    pub entity: Entity,
     pub stat_type: StatType,
-    pub amount: f32 
+    pub previous: f32,
+    pub value: f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_modifier_stacking() {
+        let mut stats = Stats::new(100.0, 0.0, 0.0, 50.0, 10.0, 0.0);
+        stats.add_modifier(StatType::Attack, StatModifier::flat(10.0, Entity::from_raw(1)));
+        stats.add_modifier(StatType::Attack, StatModifier::percent(0.5, Entity::from_raw(2)));
+        stats.add_modifier(StatType::Attack, StatModifier::percent(0.5, Entity::from_raw(3)));
+        stats.add_modifier(StatType::Attack, StatModifier::multiplicative(1.5, Entity::from_raw(4)));
+        assert_eq!(*stats.get_stat(StatType::Attack).unwrap(), 60.0);
+    }
+
+    #[test]
+    pub fn test_base_change_keeps_modifiers() {
+        let mut stats = Stats::new(100.0, 0.0, 0.0, 50.0, 10.0, 0.0);
+        stats.add_modifier(StatType::Speed, StatModifier::flat(-20.0, Entity::from_raw(1)).with_duration(1.0));
+        stats.add_stat(StatType::Speed, 10.0);
+        assert_eq!(*stats.get_stat(StatType::Speed).unwrap(), 40.0);
+        stats.tick(1.0);
+        assert_eq!(*stats.get_stat(StatType::Speed).unwrap(), 60.0);
+        assert_eq!(stats.get_base(StatType::Speed), Some(60.0));
+    }
+
+    #[test]
+    pub fn test_slow_clamps_at_zero() {
+        let mut stats = Stats::new(100.0, 0.0, 0.0, 5.0, 10.0, 0.0);
+        stats.add_modifier(StatType::Speed, StatModifier::flat(-10.0, Entity::from_raw(1)).with_duration(1.0));
+        assert_eq!(*stats.get_stat(StatType::Speed).unwrap(), 0.0);
+    }
+
+    #[test]
+    pub fn test_orphaned_modifiers_removed() {
+        let mut stats = Stats::new(100.0, 0.0, 0.0, 50.0, 10.0, 0.0);
+        let alive = Entity::from_raw(1);
+        // Same index as a despawned source but a newer generation, so it must not match
+        let recycled = Entity::from_bits(Entity::from_raw(2).to_bits() + (1 << 32));
+        stats.add_modifier(StatType::Speed, StatModifier::flat(10.0, alive));
+        stats.add_modifier(StatType::Speed, StatModifier::percent(0.5, Entity::from_raw(2)));
+        stats.add_modifier(StatType::Attack, StatModifier::flat(-5.0, Entity::from_raw(3)).with_duration(1.0));
+        stats.remove_orphaned_modifiers(|source| source == alive || source == recycled);
+        assert_eq!(*stats.get_stat(StatType::Speed).unwrap(), 60.0);
+        assert_eq!(*stats.get_stat(StatType::Attack).unwrap(), 5.0);
+    }
+
+    #[test]
+    pub fn test_same_source_refreshes() {
+        let mut stats = Stats::new(100.0, 0.0, 0.0, 50.0, 10.0, 0.0);
+        stats.add_modifier(StatType::Speed, StatModifier::flat(-10.0, Entity::from_raw(1)).with_duration(1.0));
+        stats.add_modifier(StatType::Speed, StatModifier::flat(-10.0, Entity::from_raw(1)).with_duration(1.0));
+        assert_eq!(*stats.get_stat(StatType::Speed).unwrap(), 40.0);
+        let changes = stats.drain_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0], (StatType::Speed, 50.0, 40.0));
+    }
 }
@@ -50,8 +50,8 @@ fn main() {
         .add_plugins(abilities::ability_particles::ParticlePlugin)
         .add_plugins(ui::UIPlugin)
         .add_plugins(entity::EntityPlugin)
-        .add_plugins(enemy::orc::Plugin)
         .register_type::<enemy::Enemy>()
+        .register_type::<enemy::EnemyBehaviour>()
         .add_plugins(pathfinding::PathfindingPlugin)
         .add_plugins(GamePlugin)
         .add_plugins(input::InputPlugin)
@@ -16,6 +16,20 @@ pub struct Wall {
     pub wall_type: WallType
 }
 
+/// Waypoints enemies walk between when idle, see `Patrol`
+#[derive(Component, Reflect)]
+pub struct PatrolRoute {
+    pub waypoints: Vec<Vec2>
+}
+
+/// NOTE: Kept outside the ring rocks are placed in (256 to 384 from the centre) so waypoints are never inside one
+const PATROL_ROUTES: [(&str, [Vec2; 3]); 4] = [
+    ("North East Patrol", [Vec2::new(200.0, 450.0), Vec2::new(450.0, 450.0), Vec2::new(450.0, 200.0)]),
+    ("South East Patrol", [Vec2::new(450.0, -200.0), Vec2::new(450.0, -450.0), Vec2::new(200.0, -450.0)]),
+    ("South West Patrol", [Vec2::new(-200.0, -450.0), Vec2::new(-450.0, -450.0), Vec2::new(-450.0, -200.0)]),
+    ("North West Patrol", [Vec2::new(-450.0, 200.0), Vec2::new(-450.0, 450.0), Vec2::new(-200.0, 450.0)]),
+];
+
 #[derive(Component)]
 pub struct Void;
 
@@ -26,7 +40,8 @@ pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (spawn_map, spawn_map_collision.after(spawn_map)));
+        app.register_type::<PatrolRoute>()
+           .add_systems(Startup, (spawn_map, spawn_map_collision.after(spawn_map)));
     }
 }
 
@@ -51,6 +66,10 @@ fn spawn_map(
         ..default()
     }, Name::new("Ground"))).insert_children(0, &walls);
 
+    for (name, waypoints) in PATROL_ROUTES {
+        commands.spawn((PatrolRoute { waypoints: waypoints.to_vec() }, Name::new(name)));
+    }
+
     let angles: [f32; 4] = [
         rng.gen_range(0.0..360.0),
         rng.gen_range(0.0..360.0),
@@ -89,7 +108,8 @@ fn spawn_map_collision(
     for (transform, wall) in walls.iter() {
         match wall.wall_type {
             WallType::Circle(radius) => {
-                let indices = grid.index_from_position(&transform.translation.truncate().as_ivec2());
+                let cell = grid.world_to_grid(transform.translation.truncate());
+                let indices = (cell.x as usize, cell.y as usize);
                 let u_radius = radius.remap(0.0, WORLD_SIZE.x as f32, 0.0, dim.0 as f32).round() as usize;
                 info!("Filling circle with u_radius {} from radius {}", u_radius, radius);
                 fill_circle(&mut grid, indices, u_radius);
@@ -115,6 +135,7 @@ fn spawn_map_collision(
             let _ = file.write_all(format!("{}\n", buf.join("")).as_bytes());
         }
     }
+    grid.rebuild_regions();
     info!("Constructed map with wall count: {}", count);
 }
 
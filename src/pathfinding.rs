@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::sync::Arc;
 
 use bevy::{
     prelude::*, tasks::{AsyncComputeTaskPool, Task}
@@ -6,20 +7,44 @@ use bevy::{
 use bevy_rapier2d::prelude::*;
 use futures_lite::future;
 use pathfinding::prelude::astar;
+use rand::Rng;
 
 use crate::{
-    entity::stats::{Stats, StatType},
+    entity::{hit_reaction::Stunned, stats::{Stats, StatType}},
     WORLD_SIZE,
 };
 
 pub const GRID_SIZE: i32 = 512;
 pub static GRID_TOLERANCE: f32 = (WORLD_SIZE.x / GRID_SIZE) as f32;
+/// Attempts `random_reachable_point` makes before giving up
+const RANDOM_POINT_ATTEMPTS: usize = 16;
+
+/// A cell of the `Grid`, kept apart from world positions (plain `Vec2`) so the two can't be mixed up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect)]
+pub struct GridPos {
+    pub x: i32,
+    pub y: i32
+}
+
+impl GridPos {
+    pub const fn new(x: i32, y: i32) -> Self {
+        GridPos { x, y }
+    }
+
+    pub fn as_vec2(&self) -> Vec2 {
+        Vec2::new(self.x as f32, self.y as f32)
+    }
+
+    pub fn distance_squared(&self, other: GridPos) -> i32 {
+        (self.x - other.x).pow(2) + (self.y - other.y).pow(2)
+    }
+}
 
 #[derive(Component, Reflect)]
 pub struct AITarget {
     pub follow_range: f32,
     pub attack_range: f32,
-    pub destination: Vec2,
+    pub destination: GridPos,
     pub do_path_find: bool
 }
 
@@ -28,7 +53,7 @@ impl AITarget {
         AITarget {
             follow_range,
             attack_range,
-            destination: Vec2::ZERO,
+            destination: GridPos::default(),
             do_path_find: start_pathfinding
         }
     }
@@ -37,21 +62,21 @@ impl AITarget {
 #[derive(Component, Reflect)]
 pub struct AIPath {
     pub index: usize,
-    pub points: VecDeque<IVec2>,
+    pub points: VecDeque<GridPos>,
 }
 
 impl AIPath {
-    pub fn get_target(&self) -> IVec2 {
+    pub fn get_target(&self) -> GridPos {
         self.points[self.index]
     }
 
     pub fn get_target_world(&self, grid: &Grid) -> Vec2 {
-        grid.grid_to_world_coords(&self.get_target())
+        grid.grid_to_world(self.get_target())
     }
 }
 
 pub struct Path {
-    pub steps: Vec<IVec2>,
+    pub steps: Vec<GridPos>,
 }
 
 #[derive(Debug)]
@@ -64,81 +89,70 @@ pub struct PathfindingTask {
 
 #[derive(Resource, Clone, Reflect)]
 pub struct Grid {
-    pub points: [[bool; GRID_SIZE as usize]; GRID_SIZE as usize]
+    pub points: [[bool; GRID_SIZE as usize]; GRID_SIZE as usize],
+    /// Connected area each cell belongs to, 0 for walls, empty until `rebuild_regions` is called.
+    /// NOTE: Behind an `Arc` as the grid is cloned for every pathfinding task
+    #[reflect(ignore)]
+    regions: Arc<Vec<u16>>
 }
 
-pub fn neuman_neighbours(grid: &Grid, location: &IVec2) -> Vec<IVec2> {
-    let (x, y) = (location.x as u32, location.y as u32);
-    let mut sucessors = Vec::new();
-    if let Some(left) = x.checked_sub(1) {
-        let location = IVec2::new(left as i32, y as i32);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    if let Some(down) = y.checked_sub(1) {
-        let location = IVec2::new(x as i32, down as i32);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    if x + 1 < GRID_SIZE as u32 {
-        let right = x + 1;
-        let location = IVec2::new(right as i32, y as i32);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    if y + 1 < GRID_SIZE as u32 {
-        let up = y + 1;
-        let location = IVec2::new(x as i32, up as i32);
-        if !grid.occupied(&location) {
-            sucessors.push(location);
-        }
-    }
-    sucessors
+pub fn neuman_neighbours(grid: &Grid, location: GridPos) -> Vec<GridPos> {
+    [(-1, 0), (0, -1), (1, 0), (0, 1)].into_iter()
+        .map(|(x, y)| GridPos::new(location.x + x, location.y + y))
+        .filter(|neighbour| grid.is_walkable(*neighbour))
+        .collect()
 }
 
 impl Grid {
-    pub fn path_to(&self, start: &IVec2, end: &IVec2) -> Result<Path, PathfindingError> {
+    pub fn path_to(&self, start: GridPos, end: GridPos) -> Result<Path, PathfindingError> {
         let result = astar(
-            start,
+            &start,
             |p| {
-                neuman_neighbours(self, p)
-                    .iter()
-                    .map(|neighbour| (neighbour.clone(), 1))
+                neuman_neighbours(self, *p)
+                    .into_iter()
+                    .map(|neighbour| (neighbour, 1))
                     .collect::<Vec<_>>()
             },
             |p| (p.as_vec2().distance(end.as_vec2())).round() as i32 / 3,
-            |p| p.as_vec2().distance_squared(end.as_vec2()) <= 2.0,
+            |p| p.distance_squared(end) <= 2,
         );
         if let Some((mut steps, _length)) = result {
-            steps.push(*end);
+            steps.push(end);
             Ok(Path { steps })
         } else {
             Err(PathfindingError)
         }
     }
 
-    pub fn occupied(&self, point: &IVec2) -> bool {
-        let (x, y) = self.index_from_position(&point);
-        self.points[y][x]
-    }
-
     pub fn dimensions(&self) -> (usize, usize) {
         (self.points.len(), self.points[0].len())
     }
 
-    pub fn index_from_position(&self, pos: &IVec2) -> (usize, usize) {
-        let point = *pos;
+    pub fn in_bounds(&self, cell: GridPos) -> bool {
+        let (dim_y, dim_x) = self.dimensions();
+        cell.x >= 0 && cell.y >= 0 && (cell.x as usize) < dim_x && (cell.y as usize) < dim_y
+    }
+
+    /// Cells outside the grid are never walkable
+    pub fn is_walkable(&self, cell: GridPos) -> bool {
+        self.in_bounds(cell) && !self.points[cell.y as usize][cell.x as usize]
+    }
+
+    /// The cell containing `position`, positions off the edge of the world are clamped onto it
+    pub fn world_to_grid(&self, position: Vec2) -> GridPos {
         let (dim_y, dim_x) = self.dimensions();
-        let (dim_y, dim_x) = (dim_y as i32, dim_x as i32);
-        let half_world = WORLD_SIZE / 2;
-        (
-            remap_i32(point.x, -1 * half_world.x.abs(), half_world.x.abs(), 0, dim_x - 1).clamp(0, dim_x - 1) as usize,
-            remap_i32(point.y, -1 * half_world.y.abs(), half_world.y.abs(), 0, dim_y - 1).clamp(0, dim_y - 1) as usize
+        let half_world = WORLD_SIZE.as_vec2() / 2.0;
+        let cell = ((position + half_world) / GRID_TOLERANCE).floor();
+        GridPos::new(
+            (cell.x as i32).clamp(0, dim_x as i32 - 1),
+            (cell.y as i32).clamp(0, dim_y as i32 - 1)
         )
+    }
 
+    /// Centre of `cell` in world space
+    pub fn grid_to_world(&self, cell: GridPos) -> Vec2 {
+        let half_world = WORLD_SIZE.as_vec2() / 2.0;
+        (cell.as_vec2() + 0.5) * GRID_TOLERANCE - half_world
     }
 
     pub fn set_point(&mut self, x: usize, y: usize, value: bool) {
@@ -146,52 +160,121 @@ impl Grid {
         self.points[y.clamp(0, dim_y - 1)][x.clamp(0, dim_x - 1)] = value;
     }
 
-    pub fn sample_position(&self, pos: &IVec2, dir: Vec2) -> Option<(usize, usize)> {
-        let pos = self.index_from_position(pos);
-        let dim = self.dimensions();
-        if self.occupied(&IVec2::new(pos.0 as i32, pos.1 as i32)) {
-            for y in pos.1..(if dir.y.signum() == 1.0 { dim.1 } else { 0usize }) { //This is dumb but I can't think of a better way
-                for x in pos.0..(if dir.x.signum() == 1.0 { dim.0 } else { 0usize }) { //Same but magnified
-                    if self.occupied(&IVec2::new(x as i32, y as i32)) {
-                        return Some((x, y));
+    /// Closest walkable cell to `position`, searching outward a cell at a time up to `max_distance`
+    pub fn nearest_walkable(&self, position: Vec2, max_distance: f32) -> Option<GridPos> {
+        let origin = self.world_to_grid(position);
+        let rings = (max_distance / GRID_TOLERANCE).ceil() as i32;
+        for ring in 0..=rings {
+            let mut best: Option<GridPos> = None;
+            for y in -ring..=ring {
+                for x in -ring..=ring {
+                    if x.abs() != ring && y.abs() != ring { continue; }
+                    let candidate = GridPos::new(origin.x + x, origin.y + y);
+                    if !self.is_walkable(candidate) { continue; }
+                    let distance = self.grid_to_world(candidate).distance_squared(position);
+                    if best.map_or(true, |best| distance < self.grid_to_world(best).distance_squared(position)) {
+                        best = Some(candidate);
                     }
                 }
-            };
-            None
-        } else {
-            Some(pos)
+            }
+            if best.is_some() {
+                return best;
+            }
         }
+        None
     }
 
-    pub fn grid_to_world_coords(&self, pos: &IVec2) -> Vec2 {
-        let pos = pos.as_vec2();
-        let half_world = WORLD_SIZE.as_vec2() / 2.0;
-        let (dim_y, dim_x) = self.dimensions();
-        let (dim_y, dim_x) = (dim_y as f32, dim_x as f32);
-        Vec2::new(
-            pos.x.remap(0.0, dim_x, -half_world.x, half_world.x),
-            pos.y.remap(0.0, dim_y, -half_world.y, half_world.y),
-        )
+    /// A random walkable cell within `radius` of `origin` that can be walked to from it
+    pub fn random_reachable_point(&self, origin: Vec2, radius: f32, rng: &mut impl Rng) -> Option<GridPos> {
+        let start = self.nearest_walkable(origin, radius)?;
+        for _ in 0..RANDOM_POINT_ATTEMPTS {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            // NOTE: sqrt keeps points evenly spread over the area instead of bunching at the origin
+            let distance = rng.gen::<f32>().sqrt() * radius;
+            let candidate = self.world_to_grid(origin + Vec2::from_angle(angle) * distance);
+            if self.is_walkable(candidate) && self.are_connected(start, candidate) {
+                return Some(candidate);
+            }
+        }
+        None
     }
-}
 
-pub fn remap_i32(current: i32, old_min: i32, old_max: i32, new_min: i32, new_max: i32) -> i32 {
-    lerp_i32(new_min, new_max, inv_lerp_i32(old_min, old_max, current))
-}
+    /// First blocked cell on the line from `from` to `to`, both in world space
+    pub fn raycast(&self, from: Vec2, to: Vec2) -> Option<GridPos> {
+        let (start, end) = (self.world_to_grid(from), self.world_to_grid(to));
+        let (dx, dy) = ((end.x - start.x).abs(), -(end.y - start.y).abs());
+        let (step_x, step_y) = ((end.x - start.x).signum(), (end.y - start.y).signum());
+        let mut error = dx + dy;
+        let mut cell = start;
+        loop {
+            if !self.is_walkable(cell) {
+                return Some(cell);
+            }
+            if cell == end {
+                return None;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                cell.x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                cell.y += step_y;
+            }
+        }
+    }
 
-pub fn lerp_i32(min: i32, max: i32, scalar: f32) -> i32 {
-    (min as f32 + (max - min).abs() as f32 * scalar).round() as i32
-}
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        self.raycast(from, to).is_none()
+    }
 
-pub fn inv_lerp_i32(min: i32, max: i32, current: i32) -> f32 {
-    (current - min) as f32 / (max - min) as f32
-}
+    /// Labels every connected walkable area, needs calling again whenever walls change
+    pub fn rebuild_regions(&mut self) {
+        let (dim_y, dim_x) = self.dimensions();
+        let mut regions = vec![0u16; dim_x * dim_y];
+        let mut next_region = 0u16;
+        let mut queue = VecDeque::new();
+        for y in 0..dim_y {
+            for x in 0..dim_x {
+                if self.points[y][x] || regions[y * dim_x + x] != 0 { continue; }
+                next_region = next_region.saturating_add(1);
+                regions[y * dim_x + x] = next_region;
+                queue.push_back(GridPos::new(x as i32, y as i32));
+                while let Some(cell) = queue.pop_front() {
+                    for neighbour in neuman_neighbours(self, cell) {
+                        let index = neighbour.y as usize * dim_x + neighbour.x as usize;
+                        if regions[index] == 0 {
+                            regions[index] = next_region;
+                            queue.push_back(neighbour);
+                        }
+                    }
+                }
+            }
+        }
+        self.regions = Arc::new(regions);
+    }
+
+    /// Connected area `cell` is in, `None` for walls or before `rebuild_regions` has been called
+    pub fn region(&self, cell: GridPos) -> Option<u16> {
+        if !self.is_walkable(cell) { return None; }
+        let (_, dim_x) = self.dimensions();
+        self.regions.get(cell.y as usize * dim_x + cell.x as usize).copied().filter(|region| *region != 0)
+    }
 
+    /// True if a path exists between the cells, assumes everything walkable is connected until regions are built
+    pub fn are_connected(&self, a: GridPos, b: GridPos) -> bool {
+        if !self.is_walkable(a) || !self.is_walkable(b) { return false; }
+        if self.regions.is_empty() { return true; }
+        self.region(a) == self.region(b)
+    }
+}
 
 impl Default for Grid {
     fn default() -> Self {
         Grid {
             points: [[false; GRID_SIZE as usize]; GRID_SIZE as usize],
+            regions: Arc::new(Vec::new()),
         }
     }
 }
@@ -200,11 +283,11 @@ pub fn spawn_optimized_pathfinding_task(
     commands: &mut Commands,
     target: Entity,
     grid: &Grid,
-    start: IVec2,
-    end: IVec2,
+    start: GridPos,
+    end: GridPos,
 ) {
     // Fail early if end is not valid
-    if grid.occupied(&end) {
+    if !grid.are_connected(start, end) {
         return;
     }
 
@@ -214,7 +297,7 @@ pub fn spawn_optimized_pathfinding_task(
     // Must box to prevent stack overflows on very large grids
     let grid_clone = Box::new(grid.clone());
 
-    let task = thread_pool.spawn(async move { grid_clone.path_to(&start, &end) });
+    let task = thread_pool.spawn(async move { grid_clone.path_to(start, end) });
     commands.entity(target).insert(PathfindingTask { task });
 }
 
@@ -226,17 +309,14 @@ pub fn apply_pathfinding_to_ai(
         if let Some(result) = future::block_on(future::poll_once(&mut task.task)) {
             commands.entity(task_entity).remove::<PathfindingTask>();
             if let Ok(path) = result {
-                let mut ai_path = AIPath { points: VecDeque::new(), index: 0usize };
-                for location in path.steps.iter() {
-                    ai_path.points.push_back(IVec2::new(location.x, location.y));
-                }
+                let ai_path = AIPath { points: path.steps.into_iter().collect(), index: 0usize };
                 commands.entity(task_entity).insert(ai_path);
             }
         }
     }
 }
 
-pub fn traverse_path(mut ai_pathfinders: Query<(&mut Velocity, &AITarget, &Transform, &Stats, &mut AIPath)>, grid: Res<Grid>) {
+pub fn traverse_path(mut ai_pathfinders: Query<(&mut Velocity, &AITarget, &Transform, &Stats, &mut AIPath), Without<Stunned>>, grid: Res<Grid>) {
     for (mut pathfinder, target, transform, stats, mut ai) in ai_pathfinders.iter_mut() {
         if !target.do_path_find { pathfinder.linvel = Vec2::ZERO; continue; }
         let speed = *(stats.get_stat(StatType::Speed).unwrap_or(&100.0));
@@ -256,14 +336,15 @@ pub fn calculate_paths(
     >,
 ) {
     for (entity, transform, target) in pathfinders.iter() {
-        let Some((pos_x, pos_y)) = grid.sample_position(&transform.translation.truncate().as_ivec2(), Vec2::ZERO) else { return; };
         if !target.do_path_find { continue; }
+        // NOTE: Knockback can push enemies slightly into walls, so paths start from the closest open cell
+        let Some(start) = grid.nearest_walkable(transform.translation.truncate(), GRID_TOLERANCE * 8.0) else { continue; };
         spawn_optimized_pathfinding_task(
             &mut commands,
             entity,
             &grid,
-            IVec2::new(pos_x as i32, pos_y as i32),
-            target.destination.as_ivec2(),
+            start,
+            target.destination,
         );
     }
 }
@@ -283,6 +364,7 @@ impl Plugin for PathfindingPlugin {
         );
         app.register_type::<AITarget>();
         app.register_type::<AIPath>();
+        app.register_type::<GridPos>();
     }
 }
 #[cfg(test)]
@@ -290,15 +372,37 @@ mod tests {
 
     use bevy::prelude::*;
 
-    use super::Grid;
+    use crate::entity::enemy::spawner::validate_spawn_point;
+
+    use super::{Grid, GridPos};
+
+    /// A grid with a wall of cells blocked across `x`, from `y_start` to `y_end` inclusive
+    fn walled(x: i32, y_start: i32, y_end: i32) -> Grid {
+        let mut grid = Grid::default();
+        for y in y_start..=y_end {
+            grid.set_point(x as usize, y as usize, true);
+        }
+        grid
+    }
+
+    /// A grid with a square of cells blocked `half` cells either side of the middle
+    fn blocked_middle(half: i32) -> Grid {
+        let mut grid = Grid::default();
+        let middle = grid.world_to_grid(Vec2::ZERO);
+        for y in middle.y - half..=middle.y + half {
+            for x in middle.x - half..=middle.x + half {
+                grid.set_point(x as usize, y as usize, true);
+            }
+        }
+        grid
+    }
 
     #[test]
     pub fn test_remap() {
-        let coords = IVec2::ZERO;
         let grid = Grid::default();
         let dimensions = grid.dimensions();
-        let new_coords = grid.index_from_position(&coords);
-        assert!(new_coords == (dimensions.0 / 2, dimensions.1 / 2), "({}, {}) was not midpoint ({}, {})", new_coords.0, new_coords.1, dimensions.0, dimensions.1);
+        let new_coords = grid.world_to_grid(Vec2::ZERO);
+        assert!(new_coords == GridPos::new(dimensions.0 as i32 / 2, dimensions.1 as i32 / 2), "{:?} was not midpoint ({}, {})", new_coords, dimensions.0, dimensions.1);
     }
 
     #[test]
@@ -307,7 +411,96 @@ mod tests {
         let (x, y) = grid.dimensions();
         println!("Grid dimensions {}, {} and testing centre point: (63, 63)", x, y);
         let center = (x / 2, y / 2);
-        let test_point = grid.grid_to_world_coords(&IVec2::new(x as i32 / 2, y as i32 / 2));
+        let test_point = grid.grid_to_world(GridPos::new(x as i32 / 2, y as i32 / 2));
         assert!(test_point.distance_squared(Vec2::ZERO) <= 10.0, "100% Rust bug not mine ;) {:?} (center: {},{})", test_point, center.0, center.1);
     }
+
+    #[test]
+    pub fn test_round_trip() {
+        let grid = Grid::default();
+        for cell in [GridPos::new(0, 0), GridPos::new(10, 300), GridPos::new(511, 511)] {
+            assert_eq!(grid.world_to_grid(grid.grid_to_world(cell)), cell);
+        }
+        // NOTE: Positions off the edge of the world are clamped onto it
+        assert_eq!(grid.world_to_grid(Vec2::splat(10_000.0)), GridPos::new(511, 511));
+        assert!(!grid.is_walkable(GridPos::new(-1, 0)));
+    }
+
+    #[test]
+    pub fn test_nearest_walkable() {
+        let mut grid = Grid::default();
+        for y in -8..=8 {
+            for x in -8..=8 {
+                let cell = grid.world_to_grid(Vec2::new(x as f32, y as f32));
+                grid.set_point(cell.x as usize, cell.y as usize, true);
+            }
+        }
+        assert!(!grid.is_walkable(grid.world_to_grid(Vec2::ZERO)));
+        let free = grid.nearest_walkable(Vec2::ZERO, 32.0).expect("Should find a free cell next to the rock");
+        assert!(grid.is_walkable(free));
+        assert!(grid.grid_to_world(free).length() <= 16.0, "{:?} is further than the edge of the rock", free);
+        assert_eq!(grid.nearest_walkable(Vec2::ZERO, 4.0), None);
+    }
+
+    #[test]
+    pub fn test_raycast() {
+        let grid = walled(256, 200, 300);
+        let (left, right) = (grid.grid_to_world(GridPos::new(240, 250)), grid.grid_to_world(GridPos::new(270, 250)));
+        assert_eq!(grid.raycast(left, right), Some(GridPos::new(256, 250)));
+        assert!(!grid.line_of_sight(right, left));
+        let (above_left, above_right) = (grid.grid_to_world(GridPos::new(240, 310)), grid.grid_to_world(GridPos::new(270, 310)));
+        assert!(grid.line_of_sight(above_left, above_right));
+    }
+
+    #[test]
+    pub fn test_regions() {
+        let mut grid = walled(256, 0, 511);
+        let (left, right) = (GridPos::new(100, 100), GridPos::new(400, 100));
+        // NOTE: Everything is assumed connected until regions have been built
+        assert!(grid.are_connected(left, right));
+        grid.rebuild_regions();
+        assert!(!grid.are_connected(left, right));
+        assert!(grid.are_connected(left, GridPos::new(0, 511)));
+        assert_eq!(grid.region(GridPos::new(256, 10)), None);
+        grid.set_point(256, 100, false);
+        grid.rebuild_regions();
+        assert!(grid.are_connected(left, right));
+    }
+
+    #[test]
+    pub fn test_random_reachable_point() {
+        let mut grid = walled(256, 0, 511);
+        grid.rebuild_regions();
+        let mut rng = rand::thread_rng();
+        let origin = grid.grid_to_world(GridPos::new(250, 250));
+        let points: Vec<GridPos> = (0..20).filter_map(|_| grid.random_reachable_point(origin, 64.0, &mut rng)).collect();
+        // NOTE: Most of the circle is on the origin's side of the wall so almost every attempt should find a point
+        assert!(points.len() >= 18, "only {} of 20 attempts found a point", points.len());
+        for point in points {
+            assert!(point.x < 256, "{:?} is on the other side of the wall", point);
+            assert!(grid.grid_to_world(point).distance(origin) <= 64.0 + super::GRID_TOLERANCE * 2.0);
+        }
+    }
+
+    #[test]
+    pub fn test_spawn_point_moved_out_of_rocks() {
+        let grid = blocked_middle(5);
+        let point = validate_spawn_point(&grid, Vec2::ZERO, None).expect("no walkable cell next to the rocks");
+        assert!(grid.is_walkable(grid.world_to_grid(point)), "{:?} is still inside the rocks", point);
+        assert!(point.length() <= 8.0 * super::GRID_TOLERANCE, "{:?} is further than the edge of the rocks", point);
+    }
+
+    #[test]
+    pub fn test_spawn_point_too_close_to_player() {
+        let grid = Grid::default();
+        assert_eq!(validate_spawn_point(&grid, Vec2::ZERO, Some(Vec2::new(30.0, 0.0))), None);
+        assert_eq!(validate_spawn_point(&grid, Vec2::ZERO, Some(Vec2::new(300.0, 0.0))), Some(grid.grid_to_world(grid.world_to_grid(Vec2::ZERO))));
+    }
+
+    #[test]
+    pub fn test_spawn_point_fully_blocked() {
+        // NOTE: Wider than the spawn search distance in every direction
+        let grid = blocked_middle(60);
+        assert_eq!(validate_spawn_point(&grid, Vec2::ZERO, None), None);
+    }
 }
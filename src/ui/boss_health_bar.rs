@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::entity::enemy::boss::Boss;
+use crate::entity::health::Health;
+
+const FILL_COLOUR: Color = Color::rgb(0.75, 0.1, 0.1);
+const FONT_SIZE: f32 = 24.0;
+
+pub struct BossHealthBarPlugin;
+
+impl Plugin for BossHealthBarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_boss_health_bar)
+           .add_systems(Update, update_boss_health_bar);
+    }
+}
+
+#[derive(Component)]
+struct BossHealthBar;
+
+#[derive(Component)]
+struct BossHealthBarFill;
+
+#[derive(Component)]
+struct BossTitleText;
+
+fn spawn_boss_health_bar(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        BossHealthBar,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(50.0),
+                top: Val::Percent(2.0),
+                left: Val::Percent(25.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        }
+    )).with_children(|root| {
+        root.spawn((
+            BossTitleText,
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/Alagard.ttf"),
+                    font_size: FONT_SIZE,
+                    color: Color::WHITE
+                }
+            )
+        ));
+        root.spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Px(20.0),
+                border: UiRect::all(Val::Px(2.0)),
+                ..Default::default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            background_color: BackgroundColor(Color::rgba(0.1, 0.1, 0.1, 0.75)),
+            ..Default::default()
+        }).with_children(|bar_parent| {
+            bar_parent.spawn((
+                BossHealthBarFill,
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..Default::default()
+                    },
+                    background_color: BackgroundColor(FILL_COLOUR),
+                    ..Default::default()
+                }
+            ));
+        });
+    });
+}
+
+/// Follows the first living boss, the bar is hidden while there isn't one
+fn update_boss_health_bar(
+    bosses: Query<(&Boss, &Health)>,
+    mut bar: Query<&mut Visibility, With<BossHealthBar>>,
+    mut fill: Query<&mut Style, With<BossHealthBarFill>>,
+    mut title: Query<&mut Text, With<BossTitleText>>
+) {
+    let Ok(mut visibility) = bar.get_single_mut() else { return; };
+    let Some((boss, health)) = bosses.iter().find(|(_, health)| !health.is_dead()) else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    visibility.set_if_neq(Visibility::Visible);
+    if let Ok(mut style) = fill.get_single_mut() {
+        style.width = Val::Percent(health.get_percent() * 100.0);
+    }
+    if let Ok(mut text) = title.get_single_mut() {
+        let value = format!("{} - Phase {}", boss.title, boss.phase + 1);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+
+use crate::entity::damage::DamageType;
+use crate::entity::health::{HealthDamageEvent, HealthHealEvent};
+
+const LIFETIME: f32 = 0.8;
+const DRIFT_SPEED: f32 = 24.0;
+/// DoT ticks on the same target within this window are added onto the existing number
+const DOT_MERGE_WINDOW: f32 = 0.5;
+const FONT_SIZE: f32 = 10.0;
+const CRIT_FONT_SIZE: f32 = 14.0;
+const DOT_FONT_SIZE: f32 = 8.0;
+// NOTE: Needs to be above the hanabi particles which sit at z 10-11
+const TEXT_Z: f32 = 20.0;
+
+pub struct CombatTextPlugin;
+
+impl Plugin for CombatTextPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, load_combat_text_font)
+           .add_systems(Update, (spawn_damage_text, spawn_heal_text, update_combat_text));
+    }
+}
+
+#[derive(Resource)]
+pub struct CombatTextFont(Handle<Font>);
+
+#[derive(Component)]
+pub struct CombatText {
+    pub velocity: Vec2,
+    pub remaining: f32
+}
+
+/// Running total for DoT ticks so they share one number instead of spamming a new one each tick
+#[derive(Component)]
+pub struct DotCombatText {
+    pub target: Entity,
+    pub amount: f32,
+    pub merge_remaining: f32
+}
+
+fn load_combat_text_font(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CombatTextFont(asset_server.load("fonts/Alagard.ttf")));
+}
+
+fn damage_colour(damage_type: DamageType, crit: bool) -> Color {
+    if crit {
+        return Color::rgb(1.0, 0.8, 0.1);
+    }
+    match damage_type {
+        DamageType::PHYSICAL => Color::rgb(1.0, 0.95, 0.85),
+        DamageType::MAGICAL => Color::rgb(0.7, 0.5, 1.0),
+        DamageType::BYPASS => Color::rgb(0.75, 0.75, 0.75),
+    }
+}
+
+fn combat_text_bundle(value: String, colour: Color, font_size: f32, font: &CombatTextFont, pos: Vec2) -> (Text2dBundle, CombatText) {
+    let offset = Vec2::new(rand::random::<f32>() * 16.0 - 8.0, 16.0);
+    (
+        Text2dBundle {
+            text: Text::from_section(value, TextStyle { font: font.0.clone(), font_size, color: colour })
+                .with_justify(JustifyText::Center),
+            transform: Transform::from_translation((pos + offset).extend(TEXT_Z)),
+            ..default()
+        },
+        CombatText { velocity: Vec2::Y * DRIFT_SPEED, remaining: LIFETIME }
+    )
+}
+
+fn spawn_damage_text(
+    mut commands: Commands,
+    font: Res<CombatTextFont>,
+    mut evr_damage: EventReader<HealthDamageEvent>,
+    mut dot_texts: Query<(&mut DotCombatText, &mut CombatText, &mut Text)>
+) {
+    let mut dot_totals: HashMap<Entity, (f32, DamageType, Vec2)> = HashMap::new();
+    for damage_event in evr_damage.read() {
+        if damage_event.amount <= 0.0 { continue; }
+        if damage_event.is_dot {
+            let total = dot_totals.entry(damage_event.entity).or_insert((0.0, damage_event.report.damage_type, damage_event.pos));
+            total.0 += damage_event.amount;
+            continue;
+        }
+        let crit = damage_event.report.crit;
+        let value = if crit { format!("{:.0}!", damage_event.amount) } else { format!("{:.0}", damage_event.amount) };
+        commands.spawn(combat_text_bundle(
+            value,
+            damage_colour(damage_event.report.damage_type, crit),
+            if crit { CRIT_FONT_SIZE } else { FONT_SIZE },
+            &font,
+            damage_event.pos
+        ));
+    }
+
+    for (mut dot_text, mut combat_text, mut text) in dot_texts.iter_mut() {
+        if dot_text.merge_remaining <= 0.0 { continue; }
+        let Some((amount, _, _)) = dot_totals.remove(&dot_text.target) else { continue; };
+        dot_text.amount += amount;
+        dot_text.merge_remaining = DOT_MERGE_WINDOW;
+        combat_text.remaining = LIFETIME;
+        text.sections[0].value = format!("{:.0}", dot_text.amount);
+    }
+
+    for (target, (amount, damage_type, pos)) in dot_totals {
+        commands.spawn(combat_text_bundle(format!("{:.0}", amount), damage_colour(damage_type, false).with_a(0.8), DOT_FONT_SIZE, &font, pos))
+            .insert(DotCombatText { target, amount, merge_remaining: DOT_MERGE_WINDOW });
+    }
+}
+
+fn spawn_heal_text(
+    mut commands: Commands,
+    font: Res<CombatTextFont>,
+    mut evr_heal: EventReader<HealthHealEvent>
+) {
+    for heal_event in evr_heal.read() {
+        commands.spawn(combat_text_bundle(format!("+{:.0}", heal_event.amount), Color::rgb(0.3, 1.0, 0.4), FONT_SIZE, &font, heal_event.pos));
+    }
+}
+
+fn update_combat_text(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut texts: Query<(Entity, &mut CombatText, &mut Transform, &mut Text, Option<&mut DotCombatText>)>
+) {
+    for (entity, mut combat_text, mut transform, mut text, dot_text) in texts.iter_mut() {
+        combat_text.remaining -= time.delta_seconds();
+        if combat_text.remaining <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if let Some(mut dot_text) = dot_text {
+            dot_text.merge_remaining -= time.delta_seconds();
+        }
+        transform.translation += (combat_text.velocity * time.delta_seconds()).extend(0.0);
+        let alpha = (combat_text.remaining / LIFETIME).clamp(0.0, 1.0);
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
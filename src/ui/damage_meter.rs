@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+
+use crate::entity::combat_log::CombatLog;
+use crate::player::Player;
+
+const TOGGLE_KEY: KeyCode = KeyCode::KeyM;
+const FONT_SIZE: f32 = 16.0;
+
+pub struct DamageMeterPlugin;
+
+impl Plugin for DamageMeterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_damage_meter)
+           .add_systems(Update, (toggle_damage_meter, update_damage_meter));
+    }
+}
+
+#[derive(Component)]
+struct DamageMeterPanel;
+
+#[derive(Component)]
+struct DamageMeterText;
+
+fn spawn_damage_meter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn((
+        DamageMeterPanel,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(1.0),
+                top: Val::Percent(1.0),
+                min_width: Val::Px(200.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                border: UiRect::all(Val::Px(2.0)),
+                ..Default::default()
+            },
+            border_color: BorderColor(Color::BLACK),
+            background_color: BackgroundColor(Color::rgba(0.1, 0.1, 0.1, 0.75)),
+            visibility: Visibility::Hidden,
+            ..Default::default()
+        }
+    )).with_children(|panel| {
+        panel.spawn((
+            DamageMeterText,
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/Alagard.ttf"),
+                    font_size: FONT_SIZE,
+                    color: Color::WHITE
+                }
+            )
+        ));
+    });
+}
+
+fn toggle_damage_meter(
+    input: Res<ButtonInput<KeyCode>>,
+    mut panel: Query<&mut Visibility, With<DamageMeterPanel>>
+) {
+    if !input.just_pressed(TOGGLE_KEY) { return; }
+    let Ok(mut visibility) = panel.get_single_mut() else { return; };
+    *visibility = match *visibility {
+        Visibility::Hidden => Visibility::Visible,
+        _ => Visibility::Hidden,
+    };
+}
+
+fn update_damage_meter(
+    log: Res<CombatLog>,
+    player_q: Query<Entity, With<Player>>,
+    panel: Query<Ref<Visibility>, With<DamageMeterPanel>>,
+    mut text_q: Query<&mut Text, With<DamageMeterText>>
+) {
+    let Ok(visibility) = panel.get_single() else { return; };
+    if *visibility == Visibility::Hidden { return; }
+    // NOTE: Also refresh when the panel is opened since the log may not have changed since
+    if !log.is_changed() && !visibility.is_changed() { return; }
+    let (Ok(player), Ok(mut text)) = (player_q.get_single(), text_q.get_single_mut()) else { return; };
+    let rows = log.damage_by_ability(player);
+    let mut value = String::from("Damage Meter\n");
+    if rows.is_empty() {
+        value.push_str("No damage dealt");
+    }
+    for (ability, total, dps) in rows {
+        value.push_str(&format!("{}: {:.0} ({:.1} DPS)\n", ability, total, dps));
+    }
+    text.sections[0].value = value;
+}
@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+
+use crate::entity::experience::{Experience, LevelCurve, UpgradeChosenEvent, UPGRADE_CHOICES};
+use crate::player::Player;
+
+pub struct ExperienceBarPlugin;
+
+impl Plugin for ExperienceBarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (spawn_experience_bar, spawn_upgrade_panel))
+           .add_systems(Update, (update_experience_bar, update_upgrade_panel, update_upgrade_buttons));
+    }
+}
+
+#[derive(Component)]
+struct ExperienceBarFill;
+
+#[derive(Component)]
+struct LevelText;
+
+#[derive(Component)]
+struct UpgradePanel;
+
+#[derive(Component)]
+struct UpgradeButton {
+    choice: usize
+}
+
+fn spawn_experience_bar(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(40.0),
+            height: Val::Px(16.0),
+            bottom: Val::Percent(2.0),
+            left: Val::Percent(30.0),
+            border: UiRect::all(Val::Px(2.0)),
+            ..Default::default()
+        },
+        border_color: BorderColor(Color::BLACK),
+        background_color: BackgroundColor(Color::rgba(0.1, 0.1, 0.1, 0.75)),
+        ..Default::default()
+    }).with_children(|bar_parent| {
+        bar_parent.spawn((
+            ExperienceBarFill,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(0.0),
+                    height: Val::Percent(100.0),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::rgb(0.9, 0.75, 0.2)),
+                ..Default::default()
+            }
+        ));
+    });
+    commands.spawn((
+        LevelText,
+        TextBundle::from_section(
+            "Lv 1",
+            TextStyle {
+                font: asset_server.load("fonts/Alagard.ttf"),
+                font_size: 24.0,
+                color: Color::rgb(0.9, 0.75, 0.2)
+            }
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(4.0),
+            left: Val::Percent(30.0),
+            ..Default::default()
+        })
+    ));
+}
+
+fn spawn_upgrade_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    commands.spawn(NodeBundle {
+        style: Style {
+            width: Val::Percent(60.0),
+            height: Val::Percent(20.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::SpaceEvenly,
+            align_self: AlignSelf::End,
+            justify_self: JustifySelf::Center,
+            margin: UiRect::bottom(Val::Percent(8.0)),
+            ..Default::default()
+        },
+        background_color: BackgroundColor(Color::rgba(0.8, 0.8, 0.8, 0.5)),
+        visibility: Visibility::Hidden,
+        ..Default::default()
+    }).insert(UpgradePanel)
+    .with_children(|canvas_parent| {
+        for choice in 0..UPGRADE_CHOICES {
+            canvas_parent.spawn((
+                UpgradeButton { choice },
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Percent(30.0),
+                        height: Val::Percent(80.0),
+                        border: UiRect::all(Val::Percent(1.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    background_color: BackgroundColor(Color::DARK_GRAY),
+                    ..Default::default()
+                }
+            )).with_children(|button_parent| {
+                button_parent.spawn(
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/Alagard.ttf"),
+                            font_size: 24.0,
+                            color: Color::WHITE
+                        }
+                    )
+                );
+            });
+        }
+    });
+}
+
+fn update_experience_bar(
+    curve: Res<LevelCurve>,
+    player_q: Query<&Experience, (With<Player>, Changed<Experience>)>,
+    mut fill_q: Query<&mut Style, With<ExperienceBarFill>>,
+    mut level_text_q: Query<&mut Text, With<LevelText>>
+) {
+    let Ok(experience) = player_q.get_single() else { return; };
+    if let Ok(mut fill) = fill_q.get_single_mut() {
+        fill.width = Val::Percent(experience.get_percent(&curve) * 100.0);
+    }
+    if let Ok(mut level_text) = level_text_q.get_single_mut() {
+        level_text.sections[0].value = format!("Lv {}", experience.level);
+    }
+}
+
+fn update_upgrade_panel(
+    player_q: Query<&Experience, (With<Player>, Changed<Experience>)>,
+    mut panel_q: Query<&mut Visibility, With<UpgradePanel>>,
+    buttons: Query<(&UpgradeButton, &Children)>,
+    mut texts: Query<&mut Text>
+) {
+    let Ok(experience) = player_q.get_single() else { return; };
+    let Ok(mut panel_visibility) = panel_q.get_single_mut() else { return; };
+    *panel_visibility = if experience.upgrade_choices.is_empty() { Visibility::Hidden } else { Visibility::Visible };
+    for (button, children) in buttons.iter() {
+        let Some(upgrade) = experience.upgrade_choices.get(button.choice) else { continue; };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = format!("+{} {:?}", upgrade.amount, upgrade.stat_type);
+            }
+        }
+    }
+}
+
+fn update_upgrade_buttons(
+    mut buttons: Query<(&Interaction, &mut BackgroundColor, &UpgradeButton), Changed<Interaction>>,
+    mut ev_upgrade: EventWriter<UpgradeChosenEvent>
+) {
+    for (interaction, mut background, button) in buttons.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                ev_upgrade.send(UpgradeChosenEvent { choice: button.choice });
+            },
+            Interaction::Hovered => *background = BackgroundColor(Color::rgb(0.4, 0.35, 0.2)),
+            Interaction::None => *background = BackgroundColor(Color::DARK_GRAY),
+        }
+    }
+}
@@ -3,6 +3,7 @@ use bevy::prelude::*;
 use crate::entity::health::Health;
 
 const LERP_SPEED: f32 = 0.5;
+const SHIELD_COLOUR: Color = Color::rgba(0.4, 0.85, 1.0, 0.9);
 
 #[derive(Component, Default, Reflect)]
 pub struct HealthBar {
@@ -12,6 +13,10 @@ pub struct HealthBar {
 #[derive(Component)]
 pub struct HealthBarSprite;
 
+/// Drawn behind the health bar so absorb shields show as an extension past current health
+#[derive(Component)]
+pub struct HealthBarShield;
+
 #[derive(Bundle, Default)]
 pub struct HealthBarBundle {
     pub health_bar: HealthBar,
@@ -42,7 +47,7 @@ pub struct HealthBarPlugin;
 
 impl Plugin for HealthBarPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (update_health_bar, update_health_bar_value));
+        app.add_systems(Update, (update_health_bar, update_health_bar_value, spawn_shield_overlays, update_shield_overlays));
     }
 }
 
@@ -72,6 +77,51 @@ pub fn update_health_bar(
     }
 }
 
+pub fn spawn_shield_overlays(
+    mut commands: Commands,
+    health_bars: Query<(Entity, &Handle<Image>), Added<HealthBar>>
+) {
+    for (entity, texture) in health_bars.iter() {
+        let overlay = commands.spawn((
+            HealthBarShield,
+            SpriteBundle {
+                texture: texture.clone(),
+                sprite: Sprite {
+                    color: SHIELD_COLOUR,
+                    anchor: bevy::sprite::Anchor::TopCenter,
+                    custom_size: Some(Vec2::ZERO),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, -0.1),
+                ..default()
+            }
+        )).id();
+        commands.entity(entity).add_child(overlay);
+    }
+}
+
+pub fn update_shield_overlays(
+    assets: Res<Assets<Image>>,
+    mut overlays: Query<(&mut Sprite, &Parent, &Handle<Image>), With<HealthBarShield>>,
+    health_bars: Query<(&HealthBar, &Parent)>,
+    health_entities: Query<&Health>,
+) {
+    for (mut sprite, parent, image_handle) in overlays.iter_mut() {
+        let Ok((health_bar, bar_parent)) = health_bars.get(parent.get()) else { continue; };
+        let Ok(health) = health_entities.get(bar_parent.get()) else { continue; };
+        let Some(image) = assets.get(image_handle) else { continue; };
+        let shield_percent = health.get_shield() / health.get_max();
+        let width = if shield_percent > 0.0 { (health_bar.target_value + shield_percent).min(1.0) } else { 0.0 };
+        let rect = Vec2::new(image.width() as f32 * width, image.height() as f32);
+        if sprite.custom_size == Some(rect) { continue; }
+        sprite.rect = Some(Rect {
+            min: Vec2::ZERO,
+            max: rect,
+        });
+        sprite.custom_size = Some(rect);
+    }
+}
+
 pub fn move_toward(current: f32, target: f32, max_delta: f32) -> f32 {
     if (target - current).abs() <= max_delta {
         return target;
@@ -1,5 +1,10 @@
 pub mod healthbar;
 pub mod pause;
+pub mod experience;
+pub mod combat_text;
+pub mod damage_meter;
+pub mod boss_health_bar;
+pub mod wave_display;
 
 pub struct UIPlugin;
 
@@ -7,6 +12,6 @@ use bevy::prelude::Plugin;
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_plugins((pause::PausePlugin, healthbar::HealthBarPlugin));
+        app.add_plugins((pause::PausePlugin, healthbar::HealthBarPlugin, experience::ExperienceBarPlugin, combat_text::CombatTextPlugin, damage_meter::DamageMeterPlugin, boss_health_bar::BossHealthBarPlugin, wave_display::WaveDisplayPlugin));
     }
 }
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use crate::entity::enemy::waves::{WaveCompletedEvent, WaveStartedEvent};
+
+const FONT_SIZE: f32 = 28.0;
+/// How long the wave complete banner stays up
+const BANNER_DURATION: f32 = 3.0;
+
+pub struct WaveDisplayPlugin;
+
+impl Plugin for WaveDisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_wave_display)
+           .add_systems(Update, update_wave_display);
+    }
+}
+
+#[derive(Component)]
+struct WaveCounterText;
+
+#[derive(Component)]
+struct WaveBannerText {
+    remaining: f32
+}
+
+fn spawn_wave_display(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>
+) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/Alagard.ttf"),
+        font_size: FONT_SIZE,
+        color: Color::WHITE
+    };
+    commands.spawn((
+        WaveCounterText,
+        TextBundle::from_section("", style.clone()).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(2.0),
+            left: Val::Percent(2.0),
+            ..Default::default()
+        })
+    ));
+    commands.spawn((
+        WaveBannerText { remaining: 0.0 },
+        TextBundle::from_section("", TextStyle { font_size: FONT_SIZE * 1.5, ..style }).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(30.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        }).with_text_justify(JustifyText::Center)
+    ));
+}
+
+fn update_wave_display(
+    time: Res<Time>,
+    mut evr_started: EventReader<WaveStartedEvent>,
+    mut evr_completed: EventReader<WaveCompletedEvent>,
+    mut counter: Query<&mut Text, (With<WaveCounterText>, Without<WaveBannerText>)>,
+    mut banner: Query<(&mut Text, &mut WaveBannerText), Without<WaveCounterText>>
+) {
+    let Ok((mut banner_text, mut banner)) = banner.get_single_mut() else { return; };
+    for started in evr_started.read() {
+        if let Ok(mut text) = counter.get_single_mut() {
+            text.sections[0].value = format!("Wave {}", started.wave);
+        }
+        banner_text.sections[0].value = format!("Wave {}", started.wave);
+        banner.remaining = BANNER_DURATION;
+    }
+    for completed in evr_completed.read() {
+        banner_text.sections[0].value = if completed.cleared { format!("Wave {} Cleared", completed.wave) } else { format!("Wave {} Survived", completed.wave) };
+        banner.remaining = BANNER_DURATION;
+    }
+    if banner.remaining > 0.0 {
+        banner.remaining -= time.delta_seconds();
+        if banner.remaining <= 0.0 {
+            banner_text.sections[0].value.clear();
+        }
+    }
+}